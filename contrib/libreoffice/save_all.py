@@ -0,0 +1,28 @@
+#!/usr/bin/env python3
+# Saves every modified LibreOffice document that already has a file, for TaskTide's smart save.
+# LibreOffice has to listen for UNO connections, start it with:
+#   soffice --accept="socket,host=localhost,port=2002;urp;"
+# Exits 1 if a document could not be saved (including never-saved ones), so the kill is
+# reported as unsaved.
+import sys
+
+import uno
+
+context = uno.getComponentContext()
+resolver = context.ServiceManager.createInstanceWithContext("com.sun.star.bridge.UnoUrlResolver", context)
+remote = resolver.resolve("uno:socket,host=localhost,port=2002;urp;StarOffice.ComponentContext")
+desktop = remote.ServiceManager.createInstanceWithContext("com.sun.star.frame.Desktop", remote)
+
+failed = False
+documents = desktop.Components.createEnumeration()
+while documents.hasMoreElements():
+    document = documents.nextElement()
+    if not hasattr(document, "isModified") or not document.isModified():
+        continue
+    if document.hasLocation() and not document.isReadonly():
+        document.store()
+    else:
+        print("not saved: " + document.getTitle(), file=sys.stderr)
+        failed = True
+
+sys.exit(1 if failed else 0)
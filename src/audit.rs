@@ -0,0 +1,181 @@
+use crate::config::project_dirs;
+use crate::task::DeadlineAction;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use log::error;
+
+// audit.jsonl is renamed to audit.1.jsonl once it grows past this, keeping MAX_FILES in total
+const MAX_FILE_BYTES: u64 = 1024 * 1024;
+const MAX_FILES: usize = 5;
+
+// What caused an action
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Trigger {
+    User,
+    Deadline,
+    DailyBudget,
+    FocusSession,
+    Watchdog,
+}
+
+impl Trigger {
+    // As written to the audit log and passed to hooks
+    pub fn key(self) -> &'static str {
+        match self {
+            Trigger::User => "user",
+            Trigger::Deadline => "deadline",
+            Trigger::DailyBudget => "daily_budget",
+            Trigger::FocusSession => "focus_session",
+            Trigger::Watchdog => "watchdog",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Trigger::User => "User",
+            Trigger::Deadline => "Deadline",
+            Trigger::DailyBudget => "Daily budget",
+            Trigger::FocusSession => "Focus session",
+            Trigger::Watchdog => "Watchdog",
+        }
+    }
+}
+
+// What was done to the process
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Action {
+    Terminate,
+    Suspend,
+    LowerPriority,
+    // Started by TaskTide; the entry is written once it exits
+    Launch,
+    // Relaunched by the watchdog after it went away
+    Restart,
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Action::Launch => write!(f, "Launch"),
+            Action::Restart => write!(f, "Restart"),
+            Action::Terminate => DeadlineAction::Terminate.fmt(f),
+            Action::Suspend => DeadlineAction::Suspend.fmt(f),
+            Action::LowerPriority => DeadlineAction::LowerPriority.fmt(f),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SmartSave {
+    NotAttempted,
+    // The save keys could not be sent
+    Failed,
+    Saved,
+    Unsaved,
+    // Sent, with no way to tell whether it worked
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum Outcome {
+    Succeeded,
+    Failed { error: String },
+    Refused { reason: String },
+}
+
+impl Outcome {
+    pub fn label(&self) -> String {
+        match self {
+            Outcome::Succeeded => "Done".to_string(),
+            Outcome::Failed { error } => format!("Failed: {}", error),
+            Outcome::Refused { reason } => format!("Refused: {}", reason),
+        }
+    }
+}
+
+// One line of the audit log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub started: DateTime<Local>,
+    pub finished: DateTime<Local>,
+    pub trigger: Trigger,
+    pub action: Action,
+    pub pid: u32,
+    pub name: String,
+    pub exe: String,
+    pub smart_save: SmartSave,
+    pub outcome: Outcome,
+}
+
+fn path(index: usize) -> Option<PathBuf> {
+    let file = if index == 0 {
+        "audit.jsonl".to_string()
+    } else {
+        format!("audit.{}.jsonl", index)
+    };
+    project_dirs().map(|dirs| dirs.data_dir().join(file))
+}
+
+/// Appends an entry, rotating the log first when it is full.
+pub fn record(entry: &AuditEntry) {
+    let Some(current) = path(0) else {
+        return;
+    };
+    let line = match serde_json::to_string(entry) {
+        Ok(line) => line,
+        Err(e) => {
+            error!("Failed to serialize audit entry: {}", e);
+            return;
+        }
+    };
+
+    if fs::metadata(&current).is_ok_and(|metadata| metadata.len() + line.len() as u64 >= MAX_FILE_BYTES) {
+        rotate();
+    }
+    if let Some(parent) = current.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    let written = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&current)
+        .and_then(|mut file| writeln!(file, "{}", line));
+    if let Err(e) = written {
+        error!("Failed to write audit log: {}", e);
+    }
+}
+
+fn rotate() {
+    // The oldest file is overwritten by the rename
+    for index in (0..MAX_FILES - 1).rev() {
+        if let (Some(from), Some(to)) = (path(index), path(index + 1)) {
+            if from.exists() {
+                let _ = fs::rename(from, to);
+            }
+        }
+    }
+}
+
+/// Up to `limit` entries across all log files, newest first.
+pub fn recent(limit: usize) -> Vec<AuditEntry> {
+    let mut entries = Vec::new();
+    for index in 0..MAX_FILES {
+        let Some(contents) = path(index).and_then(|path| fs::read_to_string(path).ok()) else {
+            continue;
+        };
+        // Skips lines cut short by a crash
+        entries.extend(contents.lines().rev().filter_map(|line| serde_json::from_str(line).ok()));
+        if entries.len() >= limit {
+            break;
+        }
+    }
+    entries.truncate(limit);
+    entries
+}
@@ -0,0 +1,99 @@
+use crate::audit::Trigger;
+use crate::daemon::{self, DaemonClient, Request, Response};
+use crate::scheduler::{BeforeHooks, Scheduler, Snapshot};
+use std::thread;
+use sysinfo::{System, SystemExt};
+use log::error;
+
+// Where the scheduler lives: in this process, or in a daemon we attached to
+pub enum Backend {
+    Local(Box<Scheduler>),
+    Remote(DaemonClient),
+}
+
+impl Backend {
+    pub fn connect() -> Self {
+        match DaemonClient::connect() {
+            Ok(client) => Backend::Remote(client),
+            Err(_) => Backend::Local(Box::new(Scheduler::new())),
+        }
+    }
+
+    pub fn is_remote(&self) -> bool {
+        matches!(self, Backend::Remote(_))
+    }
+
+    pub fn request(&mut self, request: Request) -> Response {
+        let result = match self {
+            Backend::Local(scheduler) => return daemon::handle(scheduler, request),
+            Backend::Remote(client) => client.request(&request),
+        };
+        match result {
+            Ok(response) => response,
+            Err(e) => {
+                // Keep enforcing deadlines even if the daemon goes away
+                error!("Lost connection to daemon, continuing locally: {}", e);
+                let mut scheduler = Box::new(Scheduler::new());
+                let response = daemon::handle(&mut scheduler, request);
+                *self = Backend::Local(scheduler);
+                response
+            }
+        }
+    }
+
+    // Before-hooks of an End Task, for the caller to run off its own thread; `None` when the
+    // daemon runs them itself
+    pub fn before_hooks(&self, pid: u32) -> Option<BeforeHooks> {
+        match self {
+            Backend::Local(scheduler) => Some(scheduler.before_hooks(pid, Trigger::User)),
+            Backend::Remote(_) => None,
+        }
+    }
+
+    // Ends a process for the user; `vetoed_by` is what its `before_hooks` returned
+    pub fn terminate(&mut self, pid: u32, vetoed_by: Option<String>) -> Response {
+        match self {
+            Backend::Local(scheduler) => {
+                daemon::done(scheduler.terminate_process(pid, true, Trigger::User, vetoed_by))
+            }
+            Backend::Remote(_) => self.request(Request::Terminate { pid, save_first: true }),
+        }
+    }
+
+    // Latest state for the GUI; a local scheduler enforces once per call
+    pub fn refresh(&mut self) -> Option<Snapshot> {
+        // The daemon ticks on its own; nobody subscribes to a local scheduler's events
+        if let Backend::Local(scheduler) = self {
+            scheduler.tick();
+            scheduler.drain_events();
+        }
+        self.snapshot()
+    }
+
+    // For `tasktide run`: a local scheduler checks the launched process's deadline and nothing
+    // else, the daemon already enforces everything
+    pub fn enforce_deadline(&mut self, pid: u32) {
+        if let Backend::Local(scheduler) = self {
+            scheduler.enforce_deadline(pid);
+            scheduler.drain_events();
+        }
+    }
+
+    // One-off state for the CLI; a local scheduler only samples, never enforces
+    pub fn sample(&mut self) -> Option<Snapshot> {
+        if let Backend::Local(scheduler) = self {
+            // CPU usage needs two samples some time apart
+            scheduler.refresh();
+            thread::sleep(System::MINIMUM_CPU_UPDATE_INTERVAL);
+            scheduler.refresh();
+        }
+        self.snapshot()
+    }
+
+    fn snapshot(&mut self) -> Option<Snapshot> {
+        match self.request(Request::Snapshot) {
+            Response::Snapshot(snapshot) => Some(*snapshot),
+            _ => None,
+        }
+    }
+}
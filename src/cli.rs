@@ -0,0 +1,401 @@
+use crate::audit::Trigger;
+use crate::backend::Backend;
+use crate::daemon::{Request, Response};
+use crate::launch;
+use crate::process_control::{self, PriorityClass};
+use crate::resource_limits::{self, ResourceLimits};
+use crate::scheduler::Snapshot;
+use crate::task::{self, Task, TimeInterval};
+use chrono::Local;
+use std::collections::HashSet;
+use std::io::{self, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Exit codes scripts can rely on
+const EXIT_OK: i32 = 0;
+const EXIT_FAILED: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+const EXIT_NO_DAEMON: i32 = 3;
+
+// How often `run` checks on its child, and how often a local scheduler checks its deadline
+const RUN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+const RUN_TICK_INTERVAL: Duration = Duration::from_secs(1);
+
+const USAGE: &str = "\
+Usage:
+  tasktide                                   Open the task manager
+  tasktide --daemon                          Enforce deadlines in the background
+  tasktide list [--sort cpu|memory|name|pid] [--json]
+  tasktide kill <pid|name> [--tree] [--save-first]
+  tasktide deadline set <pid> <duration>     Duration like 45m, 2h or 1h30m
+  tasktide deadline list [--json]
+  tasktide deadline clear [<pid>]            Clears every deadline without a PID
+  tasktide run [--limit <duration>] [--priority <class>] [--cpus <list>]
+               [--memory <size>] [--cpu-percent <n>] -- <command> [args...]
+                                             Priority idle|below_normal|normal|above_normal|high,
+                                             CPUs like 0,2-3; exits with the command's status
+  tasktide limit <pid> [--memory <size>] [--cpu-percent <n>]
+                                             Memory like 512M or 1G; no caps lifts them
+
+Every command accepts --log-level off|error|warn|info|debug|trace";
+
+enum CliError {
+    Usage(String),
+    Failed(String),
+    NoDaemon,
+    // `run` passes on its command's exit code
+    Exit(i32),
+}
+
+type CliResult = Result<(), CliError>;
+
+/// Runs a command-line invocation and returns the process exit code.
+pub fn run(args: &[String]) -> i32 {
+    let result = match args[0].as_str() {
+        "list" => list(&args[1..]),
+        "kill" => kill(&args[1..]),
+        "deadline" => deadline(&args[1..]),
+        "run" => run_command(&args[1..]),
+        "limit" => limit(&args[1..]),
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        command => Err(CliError::Usage(format!("unknown command '{}'", command))),
+    };
+    match result {
+        Ok(()) => EXIT_OK,
+        Err(CliError::Usage(message)) => {
+            eprintln!("tasktide: {}\n\n{}", message, USAGE);
+            EXIT_USAGE
+        }
+        Err(CliError::Failed(message)) => {
+            eprintln!("tasktide: {}", message);
+            EXIT_FAILED
+        }
+        Err(CliError::NoDaemon) => {
+            eprintln!("tasktide: deadlines need a running daemon, start one with `tasktide --daemon`");
+            EXIT_NO_DAEMON
+        }
+        Err(CliError::Exit(code)) => code,
+    }
+}
+
+// Positional arguments plus the flags and `--option value` pairs a command accepts
+struct ParsedArgs<'a> {
+    positional: Vec<&'a str>,
+    flags: HashSet<&'a str>,
+    options: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> ParsedArgs<'a> {
+    fn parse(args: &'a [String], flags: &[&str], options: &[&str]) -> Result<Self, CliError> {
+        let mut parsed = ParsedArgs {
+            positional: Vec::new(),
+            flags: HashSet::new(),
+            options: Vec::new(),
+        };
+        let mut args = args.iter().map(String::as_str);
+        while let Some(arg) = args.next() {
+            if flags.contains(&arg) {
+                parsed.flags.insert(arg);
+            } else if options.contains(&arg) {
+                let value = args.next()
+                    .ok_or_else(|| CliError::Usage(format!("{} needs a value", arg)))?;
+                parsed.options.push((arg, value));
+            } else if arg.starts_with("--") {
+                return Err(CliError::Usage(format!("unknown option '{}'", arg)));
+            } else {
+                parsed.positional.push(arg);
+            }
+        }
+        Ok(parsed)
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        self.flags.contains(name)
+    }
+
+    fn option(&self, name: &str) -> Option<&'a str> {
+        self.options.iter().rev().find(|(option, _)| *option == name).map(|(_, value)| *value)
+    }
+}
+
+fn sample(backend: &mut Backend) -> Result<Snapshot, CliError> {
+    backend.sample()
+        .ok_or_else(|| CliError::Failed("could not read the process list".to_string()))
+}
+
+fn parse_pid(input: &str) -> Result<u32, CliError> {
+    input.parse()
+        .map_err(|_| CliError::Usage(format!("'{}' is not a PID", input)))
+}
+
+fn print_tasks(tasks: &[Task], json: bool) -> CliResult {
+    let mut output = String::new();
+    if json {
+        output = serde_json::to_string_pretty(tasks)
+            .map_err(|e| CliError::Failed(format!("could not serialize processes: {}", e)))?;
+        output.push('\n');
+    } else {
+        output.push_str(&format!("{:>7}  {:<32} {:>6} {:>10}  {:<10} Deadline\n", "PID", "Name", "CPU", "Memory", "Status"));
+        for task in tasks {
+            output.push_str(&format!(
+                "{:>7}  {:<32} {:>5.1}% {:>7.1} MB  {:<10} {}\n",
+                task.pid,
+                task.name,
+                task.cpu_usage,
+                task.memory_usage as f64 / 1024.0 / 1024.0,
+                task.status.label(),
+                task.format_deadline(),
+            ));
+        }
+    }
+    // A closed pipe (`tasktide list | head`) is not an error
+    match io::stdout().write_all(output.as_bytes()) {
+        Err(e) if e.kind() != io::ErrorKind::BrokenPipe => {
+            Err(CliError::Failed(format!("could not write output: {}", e)))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn list(args: &[String]) -> CliResult {
+    let parsed = ParsedArgs::parse(args, &["--json"], &["--sort"])?;
+    if !parsed.positional.is_empty() {
+        return Err(CliError::Usage("list takes no arguments".to_string()));
+    }
+
+    let mut tasks = sample(&mut Backend::connect())?.tasks;
+    match parsed.option("--sort").unwrap_or("name") {
+        "cpu" => tasks.sort_by(|a, b| b.cpu_usage.total_cmp(&a.cpu_usage)),
+        "memory" | "mem" => tasks.sort_by_key(|task| std::cmp::Reverse(task.memory_usage)),
+        "name" => tasks.sort_by_key(|task| task.name.to_lowercase()),
+        "pid" => tasks.sort_by_key(|task| task.pid),
+        other => return Err(CliError::Usage(format!("cannot sort by '{}'", other))),
+    }
+    print_tasks(&tasks, parsed.flag("--json"))
+}
+
+fn kill(args: &[String]) -> CliResult {
+    let parsed = ParsedArgs::parse(args, &["--tree", "--save-first"], &[])?;
+    let [target] = parsed.positional[..] else {
+        return Err(CliError::Usage("kill needs exactly one <pid|name>".to_string()));
+    };
+
+    let mut backend = Backend::connect();
+    let tasks = sample(&mut backend)?.tasks;
+    let targets: Vec<u32> = match target.parse::<u32>() {
+        Ok(pid) => tasks.iter().filter(|task| task.pid == pid).map(|task| task.pid).collect(),
+        Err(_) => tasks.iter()
+            .filter(|task| task.name.eq_ignore_ascii_case(target))
+            .map(|task| task.pid)
+            .collect(),
+    };
+    if targets.is_empty() {
+        return Err(CliError::Failed(format!("no process matches '{}'", target)));
+    }
+
+    let save_first = parsed.flag("--save-first");
+    let mut ended = HashSet::new();
+    let mut failures = 0;
+    for pid in targets {
+        let mut victims = if parsed.flag("--tree") {
+            task::descendants(tasks.iter(), pid)
+        } else {
+            Vec::new()
+        };
+        let name = tasks.iter().find(|task| task.pid == pid).map(|task| task.name.clone()).unwrap_or_default();
+        victims.push((pid, name));
+
+        for (victim, name) in victims {
+            if !ended.insert(victim) {
+                continue;
+            }
+            match backend.request(Request::Terminate { pid: victim, save_first }) {
+                Response::Error(e) => {
+                    eprintln!("tasktide: {}", e);
+                    failures += 1;
+                }
+                _ => println!("Ended {} (PID {})", name, victim),
+            }
+        }
+    }
+
+    if failures == 0 {
+        Ok(())
+    } else {
+        Err(CliError::Failed(format!("{} process(es) could not be ended", failures)))
+    }
+}
+
+// Deadlines live in the scheduler, so only a daemon can keep them after we exit
+fn daemon() -> Result<Backend, CliError> {
+    let backend = Backend::connect();
+    if backend.is_remote() {
+        Ok(backend)
+    } else {
+        Err(CliError::NoDaemon)
+    }
+}
+
+fn expect_ok(response: Response) -> CliResult {
+    match response {
+        Response::Error(e) => Err(CliError::Failed(e.to_string())),
+        _ => Ok(()),
+    }
+}
+
+fn deadline(args: &[String]) -> CliResult {
+    let parsed = ParsedArgs::parse(args, &["--json"], &[])?;
+    match parsed.positional[..] {
+        ["set", pid, duration] => {
+            let pid = parse_pid(pid)?;
+            let duration = task::parse_duration(duration)
+                .ok_or_else(|| CliError::Usage(format!("'{}' is not a duration", duration)))?;
+            let deadline = Local::now() + duration;
+            expect_ok(daemon()?.request(Request::SetDeadline(pid, TimeInterval::Custom(deadline))))?;
+            println!("⏰ Process {} has a deadline at {}", pid, deadline.format("%H:%M:%S"));
+            Ok(())
+        }
+        ["list"] => {
+            let mut tasks: Vec<Task> = sample(&mut daemon()?)?
+                .tasks
+                .into_iter()
+                .filter(|task| task.deadline.is_some())
+                .collect();
+            tasks.sort_by_key(|task| task.deadline);
+            print_tasks(&tasks, parsed.flag("--json"))
+        }
+        ["clear", pid] => {
+            let pid = parse_pid(pid)?;
+            expect_ok(daemon()?.request(Request::ClearDeadline(pid)))
+        }
+        ["clear"] => {
+            let mut backend = daemon()?;
+            let tasks = sample(&mut backend)?.tasks;
+            for task in tasks.iter().filter(|task| task.deadline.is_some()) {
+                expect_ok(backend.request(Request::ClearDeadline(task.pid)))?;
+                println!("Cleared deadline of {} (PID {})", task.name, task.pid);
+            }
+            Ok(())
+        }
+        _ => Err(CliError::Usage("expected deadline set <pid> <duration>, deadline list or deadline clear [<pid>]".to_string())),
+    }
+}
+
+fn parse_priority(input: &str) -> Result<PriorityClass, CliError> {
+    match input {
+        "idle" => Ok(PriorityClass::Idle),
+        "below_normal" => Ok(PriorityClass::BelowNormal),
+        "normal" => Ok(PriorityClass::Normal),
+        "above_normal" => Ok(PriorityClass::AboveNormal),
+        "high" => Ok(PriorityClass::High),
+        _ => Err(CliError::Usage(format!("'{}' is not a priority class", input))),
+    }
+}
+
+fn parse_limits(parsed: &ParsedArgs) -> Result<ResourceLimits, CliError> {
+    let memory_bytes = parsed.option("--memory")
+        .map(|memory| resource_limits::parse_memory(memory).ok_or_else(|| CliError::Usage(format!("'{}' is not a memory size", memory))))
+        .transpose()?;
+    let cpu_percent = parsed.option("--cpu-percent")
+        .map(|percent| resource_limits::parse_cpu_percent(percent).ok_or_else(|| CliError::Usage(format!("'{}' is not a CPU percentage", percent))))
+        .transpose()?;
+    Ok(ResourceLimits { memory_bytes, cpu_percent })
+}
+
+fn limit(args: &[String]) -> CliResult {
+    let parsed = ParsedArgs::parse(args, &[], &["--memory", "--cpu-percent"])?;
+    let [pid] = parsed.positional[..] else {
+        return Err(CliError::Usage("limit needs exactly one <pid>".to_string()));
+    };
+    let pid = parse_pid(pid)?;
+    let limits = parse_limits(&parsed)?;
+    expect_ok(Backend::connect().request(Request::SetLimits { pid, limits }))?;
+    if limits.is_empty() {
+        println!("Lifted the limits of process {}", pid);
+    } else {
+        println!("Process {} is limited to {}", pid, limits);
+    }
+    Ok(())
+}
+
+fn run_command(args: &[String]) -> CliResult {
+    let Some(separator) = args.iter().position(|arg| arg == "--") else {
+        return Err(CliError::Usage("run needs `--` before the command".to_string()));
+    };
+    let (args, command) = (&args[..separator], &args[separator + 1..]);
+    if command.is_empty() {
+        return Err(CliError::Usage("run needs a command after `--`".to_string()));
+    }
+    let parsed = ParsedArgs::parse(args, &[], &["--limit", "--priority", "--cpus", "--memory", "--cpu-percent"])?;
+    if !parsed.positional.is_empty() {
+        return Err(CliError::Usage("options for run go before `--`".to_string()));
+    }
+    let limit = parsed.option("--limit")
+        .map(|limit| task::parse_duration(limit).ok_or_else(|| CliError::Usage(format!("'{}' is not a duration", limit))))
+        .transpose()?;
+    let priority = parsed.option("--priority").map(parse_priority).transpose()?;
+    let cpus = parsed.option("--cpus")
+        .map(|cpus| process_control::parse_cpu_list(cpus).ok_or_else(|| CliError::Usage(format!("'{}' is not a CPU list", cpus))))
+        .transpose()?;
+    let limits = parse_limits(&parsed)?;
+
+    // Without a daemon this process enforces the limit itself while it waits
+    let mut backend = Backend::connect();
+    let started = Local::now();
+    let mut child = launch::spawn(command, None, &[], true)
+        .map_err(|e| CliError::Failed(format!("could not start {}: {}", launch::program_name(command), e)))?;
+    let pid = child.id();
+    // Closing the terminal or pressing Ctrl+C takes the command down with us
+    #[cfg(windows)]
+    if let Err(e) = resource_limits::kill_on_close(pid) {
+        log::warn!("{} will keep running if tasktide is closed: {}", launch::program_name(command), e);
+    }
+
+    let mut caps = Vec::new();
+    if let Some(limit) = limit {
+        caps.push(Request::SetDeadline(pid, TimeInterval::Custom(started + limit)));
+    }
+    if let Some(priority) = priority {
+        caps.push(Request::SetPriority { pid, priority, remember: false });
+    }
+    if let Some(cpus) = cpus {
+        caps.push(Request::SetAffinity { pid, cpus, remember: false });
+    }
+    if !limits.is_empty() {
+        caps.push(Request::SetLimits { pid, limits });
+    }
+    for request in caps {
+        // Running uncapped is not what was asked for
+        if let Err(e) = expect_ok(backend.request(request)) {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(e);
+        }
+    }
+
+    let mut last_tick = Instant::now();
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) => {}
+            Err(e) => return Err(CliError::Failed(format!("lost track of process {}: {}", pid, e))),
+        }
+        if !backend.is_remote() && last_tick.elapsed() >= RUN_TICK_INTERVAL {
+            backend.enforce_deadline(pid);
+            last_tick = Instant::now();
+        }
+        thread::sleep(RUN_POLL_INTERVAL);
+    };
+
+    launch::record_exit(Trigger::User, started, pid, command, status);
+    if status.success() {
+        Ok(())
+    } else {
+        eprintln!("tasktide: {} {}", launch::program_name(command), launch::describe_exit(status));
+        Err(CliError::Exit(launch::exit_code(status)))
+    }
+}
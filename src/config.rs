@@ -0,0 +1,355 @@
+use crate::audit::Trigger;
+use crate::focus::{BROWSER_APPS, GAME_APPS};
+use crate::process_control::PriorityClass;
+use crate::smart_save::{default_profiles, parse_chord, SaveProfile};
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+use log::{error, info, warn};
+
+pub fn project_dirs() -> Option<ProjectDirs> {
+    ProjectDirs::from("com", "TaskTide", "TaskTide")
+}
+
+// Bumped whenever the meaning of an existing field changes
+pub const CONFIG_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    // Files written before versioning have none and load as 0
+    #[serde(default)]
+    pub version: u32,
+    // How often processes are sampled and deadlines enforced
+    pub refresh_interval_ms: u64,
+    // Minutes offered as one-click deadline buttons
+    pub deadline_presets: Vec<u32>,
+    // Megabytes offered as one-click memory limit buttons next to them
+    pub memory_limit_presets_mb: Vec<u64>,
+    pub colors: ColorConfig,
+    pub smart_save: SmartSaveConfig,
+    // Executable names by category; the focus blocklists refer to these
+    pub app_categories: BTreeMap<String, Vec<String>>,
+    // Daily runtime budgets in minutes, keyed by lowercase executable name
+    pub daily_budgets: HashMap<String, u32>,
+    // Extra executables blocked during every focus session
+    pub focus_blocklist: Vec<String>,
+    // Priority and CPU affinity applied whenever a matching executable starts
+    pub process_rules: HashMap<String, ProcessRule>,
+    // Commands run before and after TaskTide ends a process
+    pub termination_hooks: Vec<TerminationHook>,
+    // Executables that are relaunched when they exit or crash
+    pub watchdog_rules: Vec<WatchdogRule>,
+    // How long a process TaskTide ended can still be relaunched
+    pub relaunch_window_minutes: u32,
+    // Executables that are protected in addition to the built-in system list
+    pub protected_processes: Vec<String>,
+    // Lowercase executable names that End Task terminates without confirmation
+    pub skip_end_task_confirmation: Vec<String>,
+    // Those of them whose child processes are left running, as chosen in the dialog
+    pub end_task_keep_children: Vec<String>,
+    // Optional REST/WebSocket server run by the daemon
+    pub http_api: HttpApiConfig,
+    // Prometheus `/metrics` on the HTTP API
+    pub metrics: MetricsConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        let app_categories = [("browsers", BROWSER_APPS), ("games", GAME_APPS)]
+            .iter()
+            .map(|(category, apps)| (category.to_string(), apps.iter().map(|app| app.to_string()).collect()))
+            .collect();
+
+        Self {
+            version: CONFIG_VERSION,
+            refresh_interval_ms: 1000,
+            deadline_presets: vec![30, 60, 120],
+            memory_limit_presets_mb: vec![1024],
+            colors: ColorConfig::default(),
+            smart_save: SmartSaveConfig::default(),
+            app_categories,
+            daily_budgets: HashMap::new(),
+            focus_blocklist: Vec::new(),
+            process_rules: HashMap::new(),
+            termination_hooks: Vec::new(),
+            watchdog_rules: Vec::new(),
+            relaunch_window_minutes: 10,
+            protected_processes: Vec::new(),
+            skip_end_task_confirmation: Vec::new(),
+            end_task_keep_children: Vec::new(),
+            http_api: HttpApiConfig::default(),
+            metrics: MetricsConfig::default(),
+        }
+    }
+}
+
+// `#rrggbb` colors of the dark theme
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ColorConfig {
+    pub background: String,
+    pub surface: String,
+    pub text: String,
+    pub accent: String,
+    pub warning: String,
+    pub success: String,
+}
+
+impl Default for ColorConfig {
+    fn default() -> Self {
+        Self {
+            background: "#262626".to_string(),
+            surface: "#333333".to_string(),
+            text: "#e6e6e6".to_string(),
+            accent: "#0099ff".to_string(),
+            warning: "#cc4d4d".to_string(),
+            success: "#33cc33".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SmartSaveConfig {
+    // Apps that get a save attempt before they are ended; the first matching profile is used
+    pub profiles: Vec<SaveProfile>,
+    // Time the app gets to finish saving before it is ended
+    pub wait_after_save_ms: u64,
+    // Windows only: pause after the second save attempt, right before the process is ended
+    pub wait_before_terminate_ms: u64,
+    // Refuse to end an app unless its save could be confirmed
+    pub block_unverified: bool,
+}
+
+impl Default for SmartSaveConfig {
+    fn default() -> Self {
+        Self {
+            profiles: default_profiles(),
+            wait_after_save_ms: 2000,
+            wait_before_terminate_ms: 1000,
+            block_unverified: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TerminationHook {
+    // Lowercase executable names the hook is for; empty means every process
+    pub exe: Vec<String>,
+    // What has to have caused the kill; empty means anything
+    pub triggers: Vec<Trigger>,
+    pub before: Option<String>,
+    pub after: Option<String>,
+    // Each command is killed once it runs this long
+    pub timeout_ms: u64,
+    // `before` exiting non-zero keeps the process running
+    pub can_veto: bool,
+}
+
+impl Default for TerminationHook {
+    fn default() -> Self {
+        Self {
+            exe: Vec::new(),
+            triggers: Vec::new(),
+            before: None,
+            after: None,
+            timeout_ms: 10_000,
+            can_veto: false,
+        }
+    }
+}
+
+impl TerminationHook {
+    pub fn applies(&self, key: &str, trigger: Trigger) -> bool {
+        (self.exe.is_empty() || self.exe.iter().any(|exe| exe.eq_ignore_ascii_case(key)))
+            && (self.triggers.is_empty() || self.triggers.contains(&trigger))
+    }
+
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_ms)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WatchdogRule {
+    // Lowercase executable name, as for daily budgets
+    pub exe: String,
+    // Restarts in a row before the watchdog gives up
+    pub max_restarts: u32,
+    // Wait before the first restart, doubled for each one after it up to `max_backoff_ms`
+    pub backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    // A run at least this long resets the restart count
+    pub reset_after_ms: u64,
+}
+
+impl Default for WatchdogRule {
+    fn default() -> Self {
+        Self {
+            exe: String::new(),
+            max_restarts: 5,
+            backoff_ms: 1000,
+            max_backoff_ms: 60_000,
+            reset_after_ms: 300_000,
+        }
+    }
+}
+
+impl WatchdogRule {
+    pub fn matches(&self, key: &str) -> bool {
+        self.exe.eq_ignore_ascii_case(key)
+    }
+
+    /// How long to wait before restart number `restarts + 1`.
+    pub fn backoff(&self, restarts: u32) -> Duration {
+        let backoff = self.backoff_ms.saturating_mul(1 << restarts.min(16));
+        Duration::from_millis(backoff.min(self.max_backoff_ms))
+    }
+
+    pub fn reset_after(&self) -> Duration {
+        Duration::from_millis(self.reset_after_ms)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HttpApiConfig {
+    pub enabled: bool,
+    // Always bound to 127.0.0.1
+    pub port: u16,
+}
+
+impl Default for HttpApiConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 7878,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetricsConfig {
+    pub enabled: bool,
+    // Per-process series for the busiest processes by CPU...
+    pub top_processes: usize,
+    // ...or, when set, for these executables only
+    pub processes: Vec<String>,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            top_processes: 10,
+            processes: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProcessRule {
+    pub priority: Option<PriorityClass>,
+    pub affinity: Option<Vec<usize>>,
+}
+
+impl Config {
+    fn path() -> Option<PathBuf> {
+        project_dirs().map(|dirs| dirs.config_dir().join("config.json"))
+    }
+
+    /// When the config file was last written, to notice edits made outside the app.
+    pub fn modified() -> Option<SystemTime> {
+        Self::path()
+            .and_then(|path| fs::metadata(path).ok())
+            .and_then(|metadata| metadata.modified().ok())
+    }
+
+    pub fn load() -> Self {
+        match Self::read() {
+            Ok(config) => config,
+            Err(e) => {
+                error!("Ignoring invalid config file: {}", e);
+                Self::default()
+            }
+        }
+    }
+
+    /// Like `load`, but an invalid file (say, half-way through an edit) is an error
+    /// instead of falling back to the defaults.
+    pub fn read() -> Result<Self, serde_json::Error> {
+        let Some(contents) = Self::path().and_then(|path| fs::read_to_string(path).ok()) else {
+            return Ok(Self::default());
+        };
+        let mut config: Self = serde_json::from_str(&contents)?;
+        for profile in &config.smart_save.profiles {
+            if let Some(chord) = profile.keys.iter().find(|chord| parse_chord(chord).is_none()) {
+                warn!("Smart save profile '{}' has an invalid key '{}', its apps won't be saved", profile.name, chord);
+            }
+        }
+        if config.version < CONFIG_VERSION {
+            // Unversioned files only lack the newer settings, write them out with their defaults
+            info!("Upgrading config from version {} to {}", config.version, CONFIG_VERSION);
+            config.version = CONFIG_VERSION;
+            config.save();
+        } else if config.version > CONFIG_VERSION {
+            warn!("Config version {} is newer than this TaskTide understands, unknown settings are ignored", config.version);
+        }
+        Ok(config)
+    }
+
+    pub fn refresh_interval(&self) -> Duration {
+        // Sampling much faster than this only burns CPU
+        Duration::from_millis(self.refresh_interval_ms.max(250))
+    }
+
+    // Executable names of one `app_categories` entry
+    pub fn apps(&self, category: &str) -> &[String] {
+        self.app_categories.get(category).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    pub fn save(&self) {
+        let Some(path) = Self::path() else {
+            return;
+        };
+        if let Some(parent) = path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        match serde_json::to_string_pretty(self) {
+            Ok(contents) => {
+                if let Err(e) = fs::write(&path, contents) {
+                    error!("Failed to save config: {}", e);
+                }
+            }
+            Err(e) => error!("Failed to serialize config: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unversioned_file_gets_defaults_for_missing_settings() {
+        let config = serde_json::from_str::<Config>(r#"{"refresh_interval_ms": 500}"#).unwrap();
+        assert_eq!(config.version, 0);
+        assert_eq!(config.refresh_interval_ms, 500);
+        assert_eq!(config.smart_save.profiles, default_profiles());
+    }
+
+    #[test]
+    fn own_profiles_replace_the_builtin_ones() {
+        let contents = format!(r#"{{"version": {}, "smart_save": {{"profiles": []}}}}"#, CONFIG_VERSION);
+        let config = serde_json::from_str::<Config>(&contents).unwrap();
+        assert!(config.smart_save.profiles.is_empty());
+    }
+}
@@ -0,0 +1,322 @@
+use crate::audit::Trigger;
+use crate::config::Config;
+use crate::error::BackendError;
+use crate::focus::FocusSession;
+use crate::http;
+use crate::ipc;
+use crate::rpc;
+use crate::process_control::PriorityClass;
+use crate::resource_limits::ResourceLimits;
+use crate::scheduler::{Event, Scheduler, Snapshot};
+use crate::task::{self, DeadlineAction, TimeInterval};
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufRead, BufReader, Write};
+use std::sync::mpsc::{self, SyncSender};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::Duration;
+use log::{debug, error, info};
+
+type SharedWriter = Arc<Mutex<ipc::Stream>>;
+
+// What one tick produced, shared by every subscriber
+type Update = Arc<(Vec<Event>, Snapshot)>;
+pub type Subscribers = Arc<Mutex<Vec<SyncSender<Update>>>>;
+
+// Ticks a subscriber may fall behind before it is dropped
+const SUBSCRIBER_BACKLOG: usize = 8;
+// How long a write to a client may block before the client is given up on
+pub const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Everything a front end can ask of the scheduler, sent as one JSON object per line
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Request {
+    Snapshot,
+    Terminate { pid: u32, save_first: bool },
+    SetDeadline(u32, TimeInterval),
+    ClearDeadline(u32),
+    SetDeadlineAction(u32, DeadlineAction),
+    Suspend(u32),
+    Resume(u32),
+    SetPriority { pid: u32, priority: PriorityClass, remember: bool },
+    SetAffinity { pid: u32, cpus: Vec<usize>, remember: bool },
+    // Empty limits lift the caps again
+    SetLimits { pid: u32, limits: ResourceLimits },
+    OverrideProtection(u32, bool),
+    ToggleProtectedApp(String),
+    SetDailyBudget(String, u32),
+    ClearDailyBudget(String),
+    StartFocus { minutes: i64, blocklist: Vec<String>, require_confirmation: bool, action: DeadlineAction },
+    EndFocus { confirmation: String },
+    SkipEndTaskConfirmation { name: String, end_children: bool },
+    SaveConfig(Box<Config>),
+    Launch { command: Vec<String>, limit: Option<TimeInterval> },
+    // Starts a recently ended process again
+    Relaunch(u32),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum Response {
+    Snapshot(Box<Snapshot>),
+    Ok,
+    Launched(u32),
+    Error(BackendError),
+}
+
+pub fn done(result: Result<(), BackendError>) -> Response {
+    match result {
+        Ok(()) => Response::Ok,
+        Err(e) => Response::Error(e),
+    }
+}
+
+pub fn handle(scheduler: &mut Scheduler, request: Request) -> Response {
+    match request {
+        Request::Snapshot => Response::Snapshot(Box::new(scheduler.snapshot())),
+        Request::Terminate { pid, save_first } => {
+            let vetoed_by = scheduler.before_hooks(pid, Trigger::User).run();
+            done(scheduler.terminate_process(pid, save_first, Trigger::User, vetoed_by))
+        }
+        Request::SetDeadline(pid, interval) => {
+            done(scheduler.set_deadline(pid, interval))
+        }
+        Request::ClearDeadline(pid) => {
+            scheduler.clear_deadline(pid);
+            Response::Ok
+        }
+        Request::SetDeadlineAction(pid, action) => {
+            scheduler.set_deadline_action(pid, action);
+            Response::Ok
+        }
+        Request::Suspend(pid) => {
+            done(scheduler.suspend_process(pid))
+        }
+        Request::Resume(pid) => {
+            done(scheduler.resume_process(pid))
+        }
+        Request::SetPriority { pid, priority, remember } => {
+            let result = scheduler.set_priority(pid, priority);
+            if remember && !matches!(result, Err(BackendError::Protected { .. })) {
+                scheduler.remember_process_rule(pid, |rule| rule.priority = Some(priority));
+            }
+            done(result)
+        }
+        Request::SetAffinity { pid, cpus, remember } => {
+            let result = scheduler.set_affinity(pid, &cpus);
+            // A list that can't apply here is not worth remembering
+            if remember && !matches!(result, Err(BackendError::InvalidRequest(_) | BackendError::Protected { .. })) {
+                let all_cpus = cpus.len() == num_cpus::get();
+                scheduler.remember_process_rule(pid, |rule| {
+                    rule.affinity = if all_cpus { None } else { Some(cpus) };
+                });
+            }
+            done(result)
+        }
+        Request::SetLimits { pid, limits } => done(scheduler.set_limits(pid, limits)),
+        Request::OverrideProtection(pid, enabled) => done(scheduler.override_protection(pid, enabled)),
+        Request::ToggleProtectedApp(name) => {
+            scheduler.toggle_protected_app(&name);
+            Response::Ok
+        }
+        Request::SetDailyBudget(name, minutes) => {
+            scheduler.set_daily_budget(&name, minutes);
+            Response::Ok
+        }
+        Request::ClearDailyBudget(name) => {
+            scheduler.clear_daily_budget(&name);
+            Response::Ok
+        }
+        Request::StartFocus { minutes, blocklist, require_confirmation, action } => {
+            let Some(length) = task::minutes_duration(minutes) else {
+                return Response::Error(BackendError::InvalidRequest(format!(
+                    "a focus session of {} minutes is not between 1 minute and a year",
+                    minutes,
+                )));
+            };
+            scheduler.start_focus(FocusSession::new(length, blocklist, require_confirmation, action));
+            Response::Ok
+        }
+        Request::EndFocus { confirmation } => {
+            done(scheduler.end_focus_early(&confirmation))
+        }
+        Request::SkipEndTaskConfirmation { name, end_children } => {
+            scheduler.skip_end_task_confirmation(&name, end_children);
+            Response::Ok
+        }
+        Request::SaveConfig(config) => {
+            scheduler.save_config(*config);
+            Response::Ok
+        }
+        Request::Launch { command, limit } => match scheduler.launch(command, limit) {
+            Ok(pid) => Response::Launched(pid),
+            Err(e) => Response::Error(e),
+        },
+        Request::Relaunch(pid) => match scheduler.relaunch(pid) {
+            Ok(new_pid) => Response::Launched(new_pid),
+            Err(e) => Response::Error(e),
+        },
+    }
+}
+
+/// Ends a process for a client of the shared scheduler. Before-hooks run with it unlocked,
+/// so a slow hook holds up neither enforcement nor other clients.
+pub fn terminate(scheduler: &Mutex<Scheduler>, pid: u32, save_first: bool) -> Result<(), BackendError> {
+    let hooks = lock(scheduler).before_hooks(pid, Trigger::User);
+    let vetoed_by = hooks.run();
+    lock(scheduler).terminate_process(pid, save_first, Trigger::User, vetoed_by)
+}
+
+/// Locks the scheduler even if a handler panicked while holding it, so one bad request
+/// doesn't take the daemon down for every later client.
+pub fn lock(scheduler: &Mutex<Scheduler>) -> MutexGuard<'_, Scheduler> {
+    scheduler.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn write_message(writer: &mut impl Write, message: &impl Serialize) -> io::Result<()> {
+    let mut line = serde_json::to_string(message)?;
+    line.push('\n');
+    writer.write_all(line.as_bytes())?;
+    writer.flush()
+}
+
+// Runs the scheduler without a window until the process is stopped
+pub fn run() -> io::Result<()> {
+    let listener = ipc::bind()?;
+    let scheduler = Arc::new(Mutex::new(Scheduler::new()));
+    let subscribers: Subscribers = Arc::new(Mutex::new(Vec::new()));
+    info!("TaskTide daemon running");
+
+    let http_api = Config::load().http_api;
+    if http_api.enabled {
+        http::spawn(http_api.port, Arc::clone(&scheduler), Arc::clone(&subscribers))?;
+    }
+
+    // Enforcement keeps going whether or not a GUI is attached
+    let ticker = Arc::clone(&scheduler);
+    let ticker_subscribers = Arc::clone(&subscribers);
+    thread::spawn(move || loop {
+        let update = panic::catch_unwind(AssertUnwindSafe(|| {
+            let mut scheduler = lock(&ticker);
+            scheduler.tick();
+            (scheduler.drain_events(), scheduler.snapshot())
+        }));
+        let interval = match update {
+            Ok((events, snapshot)) => {
+                let interval = snapshot.config.refresh_interval();
+                broadcast(&ticker_subscribers, events, snapshot);
+                interval
+            }
+            // The panic message went to stderr; enforcement carries on with the next tick
+            Err(_) => {
+                error!("Scheduler tick panicked, retrying");
+                Config::default().refresh_interval()
+            }
+        };
+        thread::sleep(interval);
+    });
+
+    loop {
+        match listener.accept() {
+            Ok(stream) => {
+                let scheduler = Arc::clone(&scheduler);
+                let subscribers = Arc::clone(&subscribers);
+                thread::spawn(move || serve(stream, scheduler, subscribers));
+            }
+            Err(e) => error!("Failed to accept client: {}", e),
+        }
+    }
+}
+
+/// Has `write` called with the events and state of every tick until it fails. It runs on
+/// its own thread, so a client that stops reading holds up only itself; one that falls
+/// behind by more than a few ticks is dropped.
+pub fn subscribe(
+    subscribers: &Subscribers,
+    mut write: impl FnMut(&[Event], &Snapshot) -> io::Result<()> + Send + 'static,
+) {
+    let (sender, updates) = mpsc::sync_channel::<Update>(SUBSCRIBER_BACKLOG);
+    subscribers.lock().unwrap().push(sender);
+    thread::spawn(move || {
+        for update in updates {
+            let (events, snapshot) = &*update;
+            if let Err(e) = write(events, snapshot) {
+                debug!("Dropping subscriber: {}", e);
+                break;
+            }
+        }
+    });
+}
+
+// Never blocks: a subscriber that is gone or too far behind is removed instead
+fn broadcast(subscribers: &Subscribers, events: Vec<Event>, snapshot: Snapshot) {
+    let update = Arc::new((events, snapshot));
+    subscribers.lock().unwrap().retain(|sender| sender.try_send(Arc::clone(&update)).is_ok());
+}
+
+fn serve(stream: ipc::Stream, scheduler: Arc<Mutex<Scheduler>>, subscribers: Subscribers) {
+    let Ok(writer) = stream.try_clone() else {
+        return;
+    };
+    if let Err(e) = ipc::set_write_timeout(&writer, WRITE_TIMEOUT) {
+        debug!("Could not set a write timeout for a client: {}", e);
+    }
+    let writer: SharedWriter = Arc::new(Mutex::new(writer));
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        let written = if rpc::is_rpc(&line) {
+            let reply = rpc::handle(&scheduler, &line);
+            if reply.subscribe {
+                let writer = Arc::clone(&writer);
+                subscribe(&subscribers, move |events, _| {
+                    let mut writer = writer.lock().unwrap();
+                    events
+                        .iter()
+                        .try_for_each(|event| write_message(&mut *writer, &rpc::notification(event)))
+                });
+            }
+            match reply.response {
+                Some(response) => write_message(&mut *writer.lock().unwrap(), &response),
+                None => Ok(()),
+            }
+        } else {
+            let response = match serde_json::from_str::<Request>(&line) {
+                Ok(Request::Terminate { pid, save_first }) => done(terminate(&scheduler, pid, save_first)),
+                Ok(request) => handle(&mut lock(&scheduler), request),
+                Err(e) => Response::Error(BackendError::InvalidRequest(e.to_string())),
+            };
+            write_message(&mut *writer.lock().unwrap(), &response)
+        };
+        if written.is_err() {
+            break;
+        }
+    }
+}
+
+// Connection from a GUI or other front end to a running daemon
+pub struct DaemonClient {
+    reader: BufReader<ipc::Stream>,
+    writer: ipc::Stream,
+}
+
+impl DaemonClient {
+    pub fn connect() -> io::Result<Self> {
+        let stream = ipc::connect()?;
+        let writer = stream.try_clone()?;
+        Ok(Self {
+            reader: BufReader::new(stream),
+            writer,
+        })
+    }
+
+    pub fn request(&mut self, request: &Request) -> io::Result<Response> {
+        write_message(&mut self.writer, request)?;
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "daemon closed the connection"));
+        }
+        Ok(serde_json::from_str(&line)?)
+    }
+}
@@ -0,0 +1,71 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+
+// OS error codes that mean the PID is not (or no longer) a running process
+#[cfg(unix)]
+const NO_SUCH_PROCESS_CODES: &[i32] = &[libc::ESRCH];
+// ERROR_INVALID_PARAMETER is what OpenProcess reports for a PID that has exited
+#[cfg(windows)]
+const NO_SUCH_PROCESS_CODES: &[i32] = &[87];
+
+// Why a scheduler operation did not happen, sent to front ends as-is
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BackendError {
+    NoSuchProcess { pid: u32 },
+    Protected { pid: u32, reason: String },
+    UnsavedWork { pid: u32, reason: String },
+    HookVeto { pid: u32, command: String },
+    AccessDenied { pid: u32, code: Option<i32> },
+    Os { pid: u32, code: Option<i32>, message: String },
+    ConfirmationRequired,
+    InvalidRequest(String),
+    LaunchFailed { command: String, message: String },
+}
+
+impl BackendError {
+    pub fn from_io(pid: u32, error: &io::Error) -> Self {
+        let code = error.raw_os_error();
+        if code.is_some_and(|code| NO_SUCH_PROCESS_CODES.contains(&code)) {
+            return BackendError::NoSuchProcess { pid };
+        }
+        if error.kind() == io::ErrorKind::PermissionDenied {
+            return BackendError::AccessDenied { pid, code };
+        }
+        // The code is shown separately, drop the " (os error 5)" std appends
+        let message = error.to_string();
+        let message = match message.find(" (os error") {
+            Some(index) => message[..index].to_string(),
+            None => message,
+        };
+        BackendError::Os { pid, code, message }
+    }
+
+    /// The OS error code behind the failure, if there was one.
+    pub fn code(&self) -> Option<i32> {
+        match self {
+            BackendError::AccessDenied { code, .. } | BackendError::Os { code, .. } => *code,
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for BackendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BackendError::NoSuchProcess { pid } => write!(f, "process {} is no longer running", pid),
+            BackendError::Protected { pid, reason } => write!(f, "process {} is protected: {}", pid, reason),
+            BackendError::UnsavedWork { pid, reason } => write!(f, "process {} was not ended: {}", pid, reason),
+            BackendError::HookVeto { pid, command } => write!(f, "process {} was not ended: hook `{}` vetoed it", pid, command),
+            BackendError::AccessDenied { pid, .. } => write!(f, "access denied to process {}", pid),
+            BackendError::Os { pid, message, .. } => write!(f, "process {}: {}", pid, message),
+            BackendError::ConfirmationRequired => write!(f, "type the confirmation phrase to end the focus session early"),
+            BackendError::InvalidRequest(message) => write!(f, "invalid request: {}", message),
+            BackendError::LaunchFailed { command, message } => write!(f, "could not start {}: {}", command, message),
+        }?;
+        match self.code() {
+            Some(code) => write!(f, " (OS error {})", code),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use crate::task::DeadlineAction;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+// Phrase the user has to type to end a session early when friction is enabled
+pub const CONFIRMATION_PHRASE: &str = "END FOCUS";
+
+// Browsers offered as a one-click focus blocklist
+pub const BROWSER_APPS: &[&str] = &[
+    "chrome.exe",
+    "firefox.exe",
+    "msedge.exe",
+    "opera.exe",
+    "brave.exe",
+];
+
+// Launchers and games offered as a one-click focus blocklist
+pub const GAME_APPS: &[&str] = &[
+    "steam.exe",
+    "epicgameslauncher.exe",
+    "battle.net.exe",
+    "riotclientservices.exe",
+    "leagueclient.exe",
+    "minecraft.exe",
+    "robloxplayerbeta.exe",
+];
+
+/// Executable name to compare on, so the Windows presets match Linux processes too:
+/// `chrome`, `Chrome.exe` and `chrome.exe` are the same program, `chromedriver` is not.
+pub fn exe_stem(name: &str) -> String {
+    let name = name.trim().to_lowercase();
+    match name.strip_suffix(".exe") {
+        Some(stem) => stem.to_string(),
+        None => name,
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FocusSession {
+    pub ends_at: DateTime<Local>,
+    // Stems of the executables that are not allowed to run during the session
+    blocklist: Vec<String>,
+    pub require_confirmation: bool,
+    pub action: DeadlineAction,
+    // Processes this session froze, resumed again when it ends
+    pub suspended: HashSet<u32>,
+}
+
+impl FocusSession {
+    pub fn new(length: chrono::Duration, blocklist: Vec<String>, require_confirmation: bool, action: DeadlineAction) -> Self {
+        Self {
+            ends_at: Local::now() + length,
+            blocklist: blocklist.iter().map(|name| exe_stem(name)).collect(),
+            require_confirmation,
+            action,
+            suspended: HashSet::new(),
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        Local::now() < self.ends_at
+    }
+
+    pub fn blocks(&self, process_name: &str) -> bool {
+        self.blocklist.contains(&exe_stem(process_name))
+    }
+
+    pub fn can_end_early(&self, confirmation: &str) -> bool {
+        !self.require_confirmation || confirmation.trim() == CONFIRMATION_PHRASE
+    }
+
+    pub fn format_remaining(&self) -> String {
+        let remaining = self.ends_at.signed_duration_since(Local::now());
+        let hours = remaining.num_hours();
+        let minutes = remaining.num_minutes() % 60;
+        let seconds = remaining.num_seconds() % 60;
+        if hours > 0 {
+            format!("{}h {}m {}s left", hours, minutes, seconds)
+        } else if minutes > 0 {
+            format!("{}m {}s left", minutes, seconds)
+        } else {
+            format!("{}s left", seconds.max(0))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(blocklist: &[&str]) -> FocusSession {
+        let blocklist = blocklist.iter().map(|name| name.to_string()).collect();
+        FocusSession::new(chrono::Duration::minutes(25), blocklist, false, DeadlineAction::Terminate)
+    }
+
+    #[test]
+    fn presets_match_on_every_platform() {
+        let session = session(BROWSER_APPS);
+        for name in ["chrome", "chrome.exe", "Chrome.EXE", "firefox", "msedge"] {
+            assert!(session.blocks(name), "{}", name);
+        }
+        for name in ["chromedriver", "firefox-bin.exe", "code"] {
+            assert!(!session.blocks(name), "{}", name);
+        }
+    }
+
+    #[test]
+    fn user_entries_match_with_or_without_extension() {
+        let session = session(&["Discord", "slack.exe"]);
+        assert!(session.blocks("discord.exe"));
+        assert!(session.blocks("slack"));
+    }
+}
@@ -0,0 +1,91 @@
+use std::io::{self, Read};
+#[cfg(unix)]
+use std::os::unix::process::CommandExt;
+#[cfg(windows)]
+use std::os::windows::process::CommandExt;
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+use log::{debug, warn};
+
+// How often a running hook is checked for exit
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Runs `command` through the shell with `env` added to its environment. A hook still
+/// running after `timeout` is killed and reported as `TimedOut`.
+pub fn run(command: &str, env: &[(&str, String)], timeout: Duration) -> io::Result<ExitStatus> {
+    #[cfg(unix)]
+    let mut shell = {
+        let mut shell = Command::new("sh");
+        // Its own process group, so a timeout takes down whatever the script started too
+        shell.arg("-c").arg(command).process_group(0);
+        shell
+    };
+    #[cfg(windows)]
+    let mut shell = {
+        let mut shell = Command::new("cmd");
+        // Passed through as typed; `arg` would quote it in a way cmd doesn't understand
+        shell.arg("/C").raw_arg(command);
+        shell
+    };
+
+    debug!("Running hook `{}`", command);
+    let mut child = shell
+        .envs(env.iter().map(|(key, value)| (key, value)))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    // Drained on the side so a chatty hook can't fill the pipe and stall
+    let (stderr_sender, stderr) = mpsc::channel();
+    if let Some(mut pipe) = child.stderr.take() {
+        thread::spawn(move || {
+            let mut output = String::new();
+            let _ = pipe.read_to_string(&mut output);
+            let _ = stderr_sender.send(output);
+        });
+    }
+
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if started.elapsed() >= timeout {
+            #[cfg(unix)]
+            unsafe {
+                libc::kill(-(child.id() as i32), libc::SIGKILL);
+            }
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("timed out after {:.1}s", timeout.as_secs_f32()),
+            ));
+        }
+        thread::sleep(POLL_INTERVAL);
+    };
+
+    if !status.success() {
+        // Something the hook started in the background may hold the pipe open for good
+        let output = stderr.recv_timeout(timeout.saturating_sub(started.elapsed())).unwrap_or_default();
+        warn!("Hook `{}` exited with {}: {}", command, status, output.trim());
+    }
+    Ok(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn failing_hook_returns_while_its_background_child_holds_stderr() {
+        let started = Instant::now();
+        let status = run("sleep 5 & exit 3", &[], Duration::from_millis(500)).unwrap();
+        assert_eq!(status.code(), Some(3));
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+}
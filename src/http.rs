@@ -0,0 +1,356 @@
+// Optional REST/WebSocket server on 127.0.0.1 for dashboards. REST calls go
+// through the JSON-RPC methods; the WebSocket streams what each tick produced.
+use crate::config::project_dirs;
+use crate::daemon::{self, Subscribers};
+use crate::metrics;
+use crate::rpc::{self, RpcError};
+use crate::scheduler::Scheduler;
+use crate::websocket;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use log::{error, info};
+
+const TOKEN_FILE: &str = "api-token";
+const MAX_BODY: usize = 64 * 1024;
+// Request line and headers together
+const MAX_HEADERS: u64 = 16 * 1024;
+// How long a client gets to send its request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+struct HttpRequest {
+    method: String,
+    path: String,
+    query: HashMap<String, String>,
+    // Header names are lowercased
+    headers: HashMap<String, String>,
+    body: Vec<u8>,
+}
+
+/// Token clients must send, generated on first use and kept next to the config.
+pub fn load_or_create_token() -> io::Result<String> {
+    let dirs = project_dirs()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no config directory"))?;
+    let path = dirs.config_dir().join(TOKEN_FILE);
+    if let Ok(token) = fs::read_to_string(&path) {
+        if !token.trim().is_empty() {
+            return Ok(token.trim().to_string());
+        }
+    }
+
+    let mut bytes = [0u8; 32];
+    getrandom::getrandom(&mut bytes).map_err(|e| io::Error::other(e.to_string()))?;
+    let token: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    fs::create_dir_all(dirs.config_dir())?;
+    fs::write(&path, &token)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+    }
+    info!("HTTP API token written to {}", path.display());
+    Ok(token)
+}
+
+pub fn spawn(port: u16, scheduler: Arc<Mutex<Scheduler>>, subscribers: Subscribers) -> io::Result<()> {
+    let token = Arc::new(load_or_create_token()?);
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+    info!("HTTP API listening on http://127.0.0.1:{}", port);
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let token = Arc::clone(&token);
+                    let scheduler = Arc::clone(&scheduler);
+                    let subscribers = Arc::clone(&subscribers);
+                    // Errors only mean the client went away
+                    thread::spawn(move || {
+                        let _ = serve(stream, &token, &scheduler, &subscribers);
+                    });
+                }
+                Err(e) => error!("Failed to accept HTTP client: {}", e),
+            }
+        }
+    });
+    Ok(())
+}
+
+fn serve(
+    mut stream: TcpStream,
+    token: &str,
+    scheduler: &Mutex<Scheduler>,
+    subscribers: &Subscribers,
+) -> io::Result<()> {
+    // A client that connects and goes quiet must not hold this thread forever
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    stream.set_write_timeout(Some(daemon::WRITE_TIMEOUT))?;
+    let request = match read_request(&mut BufReader::new(stream.try_clone()?)) {
+        Ok(request) => request,
+        Err(e) => return respond_json(&mut stream, 400, &json!({ "error": e.to_string() })),
+    };
+
+    if !authorized(&request, token) {
+        return respond_json(&mut stream, 401, &json!({ "error": "missing or wrong token" }));
+    }
+
+    if request.method == "GET" && request.path == "/api/ws" {
+        return serve_websocket(stream, &request, subscribers);
+    }
+
+    if request.method == "GET" && request.path == "/metrics" {
+        let scheduler = daemon::lock(scheduler);
+        let snapshot = scheduler.snapshot();
+        if !snapshot.config.metrics.enabled {
+            return respond_json(&mut stream, 404, &json!({ "error": "metrics are disabled" }));
+        }
+        let body = metrics::render(&snapshot, &scheduler.stats());
+        drop(scheduler);
+        return respond(&mut stream, 200, metrics::CONTENT_TYPE, body.as_bytes());
+    }
+
+    let (status, body) = route(&request, scheduler);
+    respond_json(&mut stream, status, &body)
+}
+
+fn read_request(reader: &mut impl BufRead) -> io::Result<HttpRequest> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+
+    let mut head = reader.take(MAX_HEADERS);
+    let mut line = String::new();
+    head.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err(invalid("malformed request line"));
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let query = query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+
+    let mut headers = HashMap::new();
+    loop {
+        let mut header = String::new();
+        if head.read_line(&mut header)? == 0 {
+            if head.limit() == 0 {
+                return Err(invalid("request headers too large"));
+            }
+            break;
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+        }
+    }
+
+    let length: usize = headers
+        .get("content-length")
+        .map(|length| length.parse().map_err(|_| invalid("bad Content-Length")))
+        .transpose()?
+        .unwrap_or(0);
+    if length > MAX_BODY {
+        return Err(invalid("request body too large"));
+    }
+    let mut body = vec![0u8; length];
+    head.into_inner().read_exact(&mut body)?;
+
+    Ok(HttpRequest {
+        method: method.to_string(),
+        path: path.to_string(),
+        query,
+        headers,
+        body,
+    })
+}
+
+// `Authorization: Bearer <token>`, or `?token=` for browsers opening a WebSocket
+fn authorized(request: &HttpRequest, token: &str) -> bool {
+    let offered = request.headers
+        .get("authorization")
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .or_else(|| request.query.get("token").map(String::as_str))
+        .unwrap_or("");
+    // Compare every byte so the time taken doesn't reveal the token
+    offered.len() == token.len()
+        && offered.bytes().zip(token.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+fn respond_json(stream: &mut TcpStream, status: u16, body: &Value) -> io::Result<()> {
+    respond(stream, status, "application/json", body.to_string().as_bytes())
+}
+
+fn respond(stream: &mut TcpStream, status: u16, content_type: &str, body: &[u8]) -> io::Result<()> {
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        409 => "Conflict",
+        _ => "Internal Server Error",
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        reason,
+        content_type,
+        body.len()
+    )?;
+    stream.write_all(body)?;
+    stream.flush()
+}
+
+fn error_body(status: u16, message: impl Into<String>) -> (u16, Value) {
+    (status, json!({ "error": message.into() }))
+}
+
+// Maps REST routes onto the JSON-RPC methods
+fn route(request: &HttpRequest, scheduler: &Mutex<Scheduler>) -> (u16, Value) {
+    let segments: Vec<&str> = request.path.trim_matches('/').split('/').collect();
+    let body: Value = if request.body.is_empty() {
+        json!({})
+    } else {
+        match serde_json::from_slice(&request.body) {
+            Ok(body) => body,
+            Err(e) => return error_body(400, format!("invalid JSON body: {}", e)),
+        }
+    };
+    let with_pid = |pid: &str, mut params: Value| -> Result<Value, (u16, Value)> {
+        let pid: u32 = pid.parse().map_err(|_| error_body(400, format!("'{}' is not a PID", pid)))?;
+        match params.as_object_mut() {
+            Some(object) => {
+                object.insert("pid".to_string(), json!(pid));
+                Ok(params)
+            }
+            None => Err(error_body(400, "body must be a JSON object")),
+        }
+    };
+
+    let call = match (request.method.as_str(), segments.as_slice()) {
+        ("GET", ["api", "processes"]) => Ok(("list_processes", Value::Null)),
+        ("GET", ["api", "processes", pid]) => with_pid(pid, json!({})).map(|params| ("get_process", params)),
+        ("POST", ["api", "processes", pid, "terminate"]) => with_pid(pid, body).map(|params| ("terminate", params)),
+        ("GET", ["api", "deadlines"]) => Ok(("list_processes", Value::Null)),
+        ("PUT", ["api", "deadlines", pid]) => with_pid(pid, body).map(|params| ("set_deadline", params)),
+        ("DELETE", ["api", "deadlines", pid]) => with_pid(pid, json!({})).map(|params| ("clear_deadline", params)),
+        _ => Err(error_body(404, format!("no route for {} {}", request.method, request.path))),
+    };
+    let (method, params) = match call {
+        Ok(call) => call,
+        Err(error) => return error,
+    };
+
+    match rpc::dispatch(scheduler, method, params) {
+        // Deadlines are the processes that have one
+        Ok(Value::Array(tasks)) if segments == ["api", "deadlines"] => {
+            let deadlines: Vec<Value> = tasks
+                .into_iter()
+                .filter(|task| !task["deadline"].is_null())
+                .collect();
+            (200, Value::Array(deadlines))
+        }
+        Ok(result) => (200, result),
+        Err(RpcError { code, message }) => {
+            let status = match code {
+                rpc::INVALID_PARAMS => 400,
+                rpc::NO_SUCH_PROCESS => 404,
+                rpc::OPERATION_FAILED => 409,
+                _ => 500,
+            };
+            error_body(status, message)
+        }
+    }
+}
+
+// Streams `{"type": "snapshot", ...}` every tick and `{"type": "event", ...}` as things happen
+fn serve_websocket(mut stream: TcpStream, request: &HttpRequest, subscribers: &Subscribers) -> io::Result<()> {
+    let Some(key) = request.headers.get("sec-websocket-key") else {
+        return respond_json(&mut stream, 400, &json!({ "error": "expected a WebSocket upgrade" }));
+    };
+    write!(
+        stream,
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        websocket::accept_key(key)
+    )?;
+    // Clients may stay quiet for as long as they like once subscribed
+    stream.set_read_timeout(None)?;
+
+    let writer = Arc::new(Mutex::new(stream.try_clone()?));
+    let sink_writer = Arc::clone(&writer);
+    daemon::subscribe(subscribers, move |events, snapshot| {
+        let mut writer = sink_writer.lock().unwrap();
+        let mut send = |message: Value| {
+            websocket::write_frame(&mut *writer, websocket::OPCODE_TEXT, message.to_string().as_bytes())
+        };
+        send(json!({ "type": "snapshot", "tasks": snapshot.tasks, "focus": snapshot.focus }))?;
+        events.iter().try_for_each(|event| send(json!({ "type": "event", "event": event })))
+    });
+
+    // The client only ever sends control frames
+    let result = loop {
+        match websocket::read_frame(&mut stream) {
+            Ok((websocket::OPCODE_CLOSE, payload)) => {
+                let _ = websocket::write_frame(&mut *writer.lock().unwrap(), websocket::OPCODE_CLOSE, &payload);
+                break Ok(());
+            }
+            Ok((websocket::OPCODE_PING, payload)) => {
+                websocket::write_frame(&mut *writer.lock().unwrap(), websocket::OPCODE_PONG, &payload)?;
+            }
+            Ok(_) => {}
+            Err(e) => break Err(e),
+        }
+    };
+    // Makes the next write fail so the subscription is dropped
+    let _ = stream.shutdown(std::net::Shutdown::Both);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(raw: &[u8]) -> io::Result<HttpRequest> {
+        read_request(&mut BufReader::new(raw))
+    }
+
+    #[test]
+    fn parses_request_line_headers_and_body() {
+        let request = parse(
+            b"POST /api/tasks?token=abc&x=1 HTTP/1.1\r\nContent-Type: application/json\r\nContent-Length: 2\r\n\r\n{}",
+        )
+        .unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/api/tasks");
+        assert_eq!(request.query.get("token").map(String::as_str), Some("abc"));
+        assert_eq!(request.headers.get("content-type").map(String::as_str), Some("application/json"));
+        assert_eq!(request.body, b"{}");
+    }
+
+    #[test]
+    fn rejects_oversized_headers() {
+        let mut raw = b"GET / HTTP/1.1\r\n".to_vec();
+        while raw.len() as u64 <= MAX_HEADERS {
+            raw.extend_from_slice(b"X-Filler: aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa\r\n");
+        }
+        raw.extend_from_slice(b"\r\n");
+        assert!(matches!(parse(&raw), Err(error) if error.kind() == io::ErrorKind::InvalidData));
+    }
+
+    #[test]
+    fn rejects_bad_or_oversized_bodies() {
+        assert!(parse(b"POST / HTTP/1.1\r\nContent-Length: nope\r\n\r\n").is_err());
+        let oversized = format!("POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n", MAX_BODY + 1);
+        assert!(parse(oversized.as_bytes()).is_err());
+        assert!(parse(b"\r\n").is_err());
+    }
+}
@@ -0,0 +1,268 @@
+// Local transport between the daemon and its clients: a Unix socket on Linux,
+// a named pipe on Windows. Neither is reachable from other machines.
+
+#[cfg(unix)]
+mod imp {
+    use crate::config::project_dirs;
+    use std::fs;
+    use std::io;
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    pub type Stream = UnixStream;
+
+    pub struct Listener(UnixListener);
+
+    // $XDG_RUNTIME_DIR/tasktide when available, the data directory otherwise
+    pub fn socket_path() -> PathBuf {
+        let dir = project_dirs()
+            .map(|dirs| dirs.runtime_dir().unwrap_or(dirs.data_dir()).to_path_buf())
+            .unwrap_or_else(std::env::temp_dir);
+        dir.join("tasktide.sock")
+    }
+
+    pub fn bind() -> io::Result<Listener> {
+        let path = socket_path();
+        if path.exists() {
+            // A live daemon still answers; otherwise the socket is left over from a crash
+            if UnixStream::connect(&path).is_ok() {
+                return Err(io::Error::new(
+                    io::ErrorKind::AddrInUse,
+                    "another TaskTide daemon is already running",
+                ));
+            }
+            fs::remove_file(&path)?;
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // Only the owning user may talk to the daemon. The umask makes the socket private
+        // from the moment it exists; the daemon has no other threads yet to be affected.
+        let umask = unsafe { libc::umask(0o177) };
+        let listener = UnixListener::bind(&path);
+        unsafe { libc::umask(umask) };
+        let listener = listener?;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+        Ok(Listener(listener))
+    }
+
+    impl Listener {
+        pub fn accept(&self) -> io::Result<Stream> {
+            self.0.accept().map(|(stream, _)| stream)
+        }
+    }
+
+    pub fn connect() -> io::Result<Stream> {
+        UnixStream::connect(socket_path())
+    }
+
+    pub fn set_write_timeout(stream: &Stream, timeout: Duration) -> io::Result<()> {
+        stream.set_write_timeout(Some(timeout))
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use std::fs::OpenOptions;
+    use std::io::{self, Read, Write};
+    use std::os::windows::fs::OpenOptionsExt;
+    use std::os::windows::io::IntoRawHandle;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use windows::core::PCWSTR;
+    use windows::Win32::Foundation::{
+        CloseHandle, BOOL, ERROR_ACCESS_DENIED, ERROR_BROKEN_PIPE, ERROR_IO_PENDING,
+        ERROR_PIPE_CONNECTED, HANDLE, WAIT_TIMEOUT,
+    };
+    use windows::Win32::Storage::FileSystem::{
+        ReadFile, WriteFile, FILE_FLAG_FIRST_PIPE_INSTANCE, FILE_FLAG_OVERLAPPED, PIPE_ACCESS_DUPLEX,
+    };
+    use windows::Win32::System::Pipes::{
+        ConnectNamedPipe, CreateNamedPipeW, PIPE_READMODE_BYTE, PIPE_REJECT_REMOTE_CLIENTS,
+        PIPE_TYPE_BYTE, PIPE_UNLIMITED_INSTANCES, PIPE_WAIT,
+    };
+    use windows::Win32::System::IO::{CancelIoEx, GetOverlappedResult, OVERLAPPED};
+    use windows::Win32::System::Threading::{CreateEventW, WaitForSingleObject, INFINITE};
+
+    const PIPE_NAME: &str = r"\\.\pipe\tasktide";
+
+    struct Pipe {
+        handle: HANDLE,
+        write_timeout: Mutex<Option<Duration>>,
+    }
+
+    // The handle is only used through overlapped calls, which may run on several threads at once
+    unsafe impl Send for Pipe {}
+    unsafe impl Sync for Pipe {}
+
+    impl Drop for Pipe {
+        fn drop(&mut self) {
+            unsafe {
+                let _ = CloseHandle(self.handle);
+            }
+        }
+    }
+
+    /// One end of a pipe opened for overlapped I/O, so one thread can write while another
+    /// is blocked reading the same connection.
+    pub struct Stream(Arc<Pipe>);
+
+    impl Stream {
+        fn new(handle: HANDLE) -> Self {
+            Stream(Arc::new(Pipe { handle, write_timeout: Mutex::new(None) }))
+        }
+
+        pub fn try_clone(&self) -> io::Result<Stream> {
+            Ok(Stream(Arc::clone(&self.0)))
+        }
+
+        pub fn set_write_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+            *self.0.write_timeout.lock().unwrap() = timeout;
+            Ok(())
+        }
+
+        // Starts an operation with its own event and waits for it, cancelling it after `timeout`
+        fn overlapped(
+            &self,
+            timeout: Option<Duration>,
+            start: impl FnOnce(*mut OVERLAPPED) -> BOOL,
+        ) -> io::Result<usize> {
+            let handle = self.0.handle;
+            unsafe {
+                let event = CreateEventW(None, true, false, PCWSTR::null())
+                    .map_err(|e| io::Error::from_raw_os_error(e.code().0 & 0xFFFF))?;
+                let mut overlapped = OVERLAPPED { hEvent: event, ..Default::default() };
+                let result = if start(&mut overlapped).as_bool()
+                    || io::Error::last_os_error().raw_os_error() == Some(ERROR_IO_PENDING.0 as i32)
+                {
+                    let millis = timeout.map_or(INFINITE, |timeout| timeout.as_millis().min(u32::MAX as u128 - 1) as u32);
+                    let timed_out = WaitForSingleObject(event, millis) == WAIT_TIMEOUT;
+                    if timed_out {
+                        let _ = CancelIoEx(handle, Some(&overlapped as *const OVERLAPPED));
+                    }
+                    // Waits for a cancelled operation too, the buffer must outlive it
+                    let mut transferred = 0;
+                    if timed_out {
+                        let _ = GetOverlappedResult(handle, &overlapped, &mut transferred, true);
+                        Err(io::Error::new(io::ErrorKind::TimedOut, "pipe write timed out"))
+                    } else if GetOverlappedResult(handle, &overlapped, &mut transferred, true).as_bool() {
+                        Ok(transferred as usize)
+                    } else {
+                        Err(io::Error::last_os_error())
+                    }
+                } else {
+                    Err(io::Error::last_os_error())
+                };
+                let _ = CloseHandle(event);
+                result
+            }
+        }
+    }
+
+    impl Read for Stream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let handle = self.0.handle;
+            let length = buf.len().min(u32::MAX as usize) as u32;
+            let result = self.overlapped(None, |overlapped| unsafe {
+                ReadFile(handle, Some(buf.as_mut_ptr().cast()), length, None, Some(overlapped))
+            });
+            match result {
+                // The other end closing is the end of the stream
+                Err(e) if e.raw_os_error() == Some(ERROR_BROKEN_PIPE.0 as i32) => Ok(0),
+                result => result,
+            }
+        }
+    }
+
+    impl Write for Stream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let handle = self.0.handle;
+            let buf = &buf[..buf.len().min(u32::MAX as usize)];
+            let timeout = *self.0.write_timeout.lock().unwrap();
+            self.overlapped(timeout, |overlapped| unsafe {
+                WriteFile(handle, Some(buf), None, Some(overlapped))
+            })
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // The instance waiting for the next client; there is always one, so a second daemon
+    // can't create the pipe
+    pub struct Listener(Mutex<Stream>);
+
+    fn create_instance(first: bool) -> io::Result<Stream> {
+        let name: Vec<u16> = PIPE_NAME.encode_utf16().chain(std::iter::once(0)).collect();
+        let mut mode = PIPE_ACCESS_DUPLEX | FILE_FLAG_OVERLAPPED;
+        if first {
+            mode |= FILE_FLAG_FIRST_PIPE_INSTANCE;
+        }
+        unsafe {
+            let handle = CreateNamedPipeW(
+                PCWSTR::from_raw(name.as_ptr()),
+                mode,
+                PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS,
+                PIPE_UNLIMITED_INSTANCES,
+                4096,
+                4096,
+                0,
+                None,
+            );
+            if handle.is_invalid() {
+                let error = io::Error::last_os_error();
+                if first && error.raw_os_error() == Some(ERROR_ACCESS_DENIED.0 as i32) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::AddrInUse,
+                        "another TaskTide daemon is already running",
+                    ));
+                }
+                return Err(error);
+            }
+            Ok(Stream::new(handle))
+        }
+    }
+
+    pub fn bind() -> io::Result<Listener> {
+        Ok(Listener(Mutex::new(create_instance(true)?)))
+    }
+
+    impl Listener {
+        // Every client gets its own pipe instance
+        pub fn accept(&self) -> io::Result<Stream> {
+            let mut pending = self.0.lock().unwrap();
+            let handle = pending.0.handle;
+            let connected = pending.overlapped(None, |overlapped| unsafe {
+                ConnectNamedPipe(handle, Some(overlapped))
+            });
+            match connected {
+                Ok(_) => {}
+                // A client that connected before we started waiting still counts
+                Err(e) if e.raw_os_error() == Some(ERROR_PIPE_CONNECTED.0 as i32) => {}
+                Err(e) => {
+                    *pending = create_instance(false)?;
+                    return Err(e);
+                }
+            }
+            Ok(std::mem::replace(&mut *pending, create_instance(false)?))
+        }
+    }
+
+    pub fn connect() -> io::Result<Stream> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(FILE_FLAG_OVERLAPPED.0)
+            .open(PIPE_NAME)?;
+        Ok(Stream::new(HANDLE(file.into_raw_handle() as isize)))
+    }
+
+    pub fn set_write_timeout(stream: &Stream, timeout: Duration) -> io::Result<()> {
+        stream.set_write_timeout(Some(timeout))
+    }
+}
+
+pub use imp::{bind, connect, set_write_timeout, Stream};
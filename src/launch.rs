@@ -0,0 +1,150 @@
+use crate::audit::{self, Action, AuditEntry, Outcome, SmartSave, Trigger};
+use chrono::{DateTime, Local};
+use std::io;
+use std::path::Path;
+use std::process::{Child, Command, ExitStatus, Stdio};
+#[cfg(unix)]
+use std::os::unix::process::ExitStatusExt;
+
+/// Splits a typed command line into the program and its arguments. Single and double
+/// quotes group words; `None` if a quote is left open.
+pub fn split_command(line: &str) -> Option<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote = None;
+    for c in line.chars() {
+        match quote {
+            Some(open) if c == open => quote = None,
+            Some(_) => word.push(c),
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                in_word = true;
+            }
+            None if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            None => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if quote.is_some() {
+        return None;
+    }
+    if in_word {
+        words.push(word);
+    }
+    Some(words)
+}
+
+/// Starts `command`, in `cwd` if given, with exactly `env` unless it is empty. The CLI
+/// passes its terminal through; the scheduler has none to give.
+pub fn spawn(command: &[String], cwd: Option<&Path>, env: &[(String, String)], inherit_stdio: bool) -> io::Result<Child> {
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty command"))?;
+    let mut process = Command::new(program);
+    process.args(args);
+    if let Some(cwd) = cwd {
+        process.current_dir(cwd);
+    }
+    if !env.is_empty() {
+        process.env_clear().envs(env.iter().map(|(key, value)| (key, value)));
+    }
+    if !inherit_stdio {
+        process.stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null());
+    }
+    process.spawn()
+}
+
+pub fn describe_exit(status: ExitStatus) -> String {
+    #[cfg(unix)]
+    if let Some(signal) = status.signal() {
+        return format!("was killed by signal {}", signal);
+    }
+    match status.code() {
+        Some(0) => "exited normally".to_string(),
+        Some(code) => format!("exited with status {}", code),
+        None => "exited".to_string(),
+    }
+}
+
+/// The code a shell would report, 128 + the signal for a killed process.
+pub fn exit_code(status: ExitStatus) -> i32 {
+    #[cfg(unix)]
+    if let Some(signal) = status.signal() {
+        return 128 + signal;
+    }
+    status.code().unwrap_or(1)
+}
+
+pub fn program_name(command: &[String]) -> String {
+    command
+        .first()
+        .map(|program| {
+            Path::new(program)
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| program.clone())
+        })
+        .unwrap_or_default()
+}
+
+/// Adds the finished run to the audit log.
+pub fn record_exit(trigger: Trigger, started: DateTime<Local>, pid: u32, command: &[String], status: ExitStatus) {
+    let outcome = if status.success() {
+        Outcome::Succeeded
+    } else {
+        Outcome::Failed { error: describe_exit(status) }
+    };
+    audit::record(&AuditEntry {
+        started,
+        finished: Local::now(),
+        trigger,
+        action: Action::Launch,
+        pid,
+        name: program_name(command),
+        exe: command.first().cloned().unwrap_or_default(),
+        smart_save: SmartSave::NotAttempted,
+        outcome,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(line: &str) -> Option<Vec<String>> {
+        split_command(line)
+    }
+
+    #[test]
+    fn splits_on_whitespace() {
+        assert_eq!(words("  ls  -la\t/tmp "), Some(vec!["ls".into(), "-la".into(), "/tmp".into()]));
+        assert_eq!(words(""), Some(vec![]));
+        assert_eq!(words("   "), Some(vec![]));
+    }
+
+    #[test]
+    fn quotes_group_words() {
+        assert_eq!(
+            words(r#"code "My Project" 'it''s' a"b c"d"#),
+            Some(vec!["code".into(), "My Project".into(), "its".into(), "ab cd".into()])
+        );
+        assert_eq!(words(r#"echo "it's""#), Some(vec!["echo".into(), "it's".into()]));
+        // An empty quoted argument is still an argument
+        assert_eq!(words(r#"grep """#), Some(vec!["grep".into(), String::new()]));
+    }
+
+    #[test]
+    fn rejects_unterminated_quotes() {
+        assert_eq!(words(r#"echo "oops"#), None);
+        assert_eq!(words("echo 'oops"), None);
+    }
+}
+
@@ -0,0 +1,154 @@
+use crate::config::project_dirs;
+use chrono::Local;
+use log::{LevelFilter, Log, Metadata, Record};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+// tasktide.log is renamed to tasktide.1.log once it grows past this, keeping MAX_FILES in total
+const MAX_FILE_BYTES: u64 = 5 * 1024 * 1024;
+const MAX_FILES: usize = 3;
+
+const LEVELS: &str = "off, error, warn, info, debug or trace";
+
+struct LogFile {
+    file: File,
+    len: u64,
+}
+
+// Writes every record to stderr and to the log file in the data directory
+struct Logger {
+    level: LevelFilter,
+    file: Mutex<Option<LogFile>>,
+}
+
+fn path(index: usize) -> Option<PathBuf> {
+    let file = if index == 0 {
+        "tasktide.log".to_string()
+    } else {
+        format!("tasktide.{}.log", index)
+    };
+    project_dirs().map(|dirs| dirs.data_dir().join("logs").join(file))
+}
+
+fn open() -> Option<LogFile> {
+    let path = path(0)?;
+    fs::create_dir_all(path.parent()?).ok()?;
+    let file = OpenOptions::new().create(true).append(true).open(&path).ok()?;
+    let len = file.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+    Some(LogFile { file, len })
+}
+
+fn rotate() {
+    for index in (0..MAX_FILES - 1).rev() {
+        if let (Some(from), Some(to)) = (path(index), path(index + 1)) {
+            if from.exists() {
+                let _ = fs::rename(from, to);
+            }
+        }
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = format!(
+            "{} {:<5} {}: {}\n",
+            Local::now().format("%Y-%m-%dT%H:%M:%S%.3f%:z"),
+            record.level(),
+            record.target(),
+            record.args()
+        );
+        let _ = std::io::stderr().write_all(line.as_bytes());
+
+        let mut file = self.file.lock().unwrap();
+        if file.as_ref().is_some_and(|log| log.len + line.len() as u64 > MAX_FILE_BYTES) {
+            *file = None;
+            rotate();
+            *file = open();
+        }
+        if let Some(log) = file.as_mut() {
+            if log.file.write_all(line.as_bytes()).is_ok() {
+                log.len += line.len() as u64;
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(log) = self.file.lock().unwrap().as_mut() {
+            let _ = log.file.flush();
+        }
+    }
+}
+
+/// Installs the logger; records above `level` are dropped.
+pub fn init(level: LevelFilter) {
+    let logger = Logger {
+        level,
+        file: Mutex::new(open()),
+    };
+    if log::set_boxed_logger(Box::new(logger)).is_ok() {
+        log::set_max_level(level);
+    }
+}
+
+/// Removes `--log-level <level>` (or `--log-level=<level>`) from the arguments. Anything
+/// after `--` belongs to the program being run and is left alone.
+pub fn take_level_arg(args: &mut Vec<String>) -> Result<Option<LevelFilter>, String> {
+    let Some(index) = args
+        .iter()
+        .take_while(|arg| *arg != "--")
+        .position(|arg| arg == "--log-level" || arg.starts_with("--log-level="))
+    else {
+        return Ok(None);
+    };
+    let arg = args.remove(index);
+    let value = match arg.strip_prefix("--log-level=") {
+        Some(value) => value.to_string(),
+        None if index < args.len() => args.remove(index),
+        None => return Err(format!("--log-level needs one of {}", LEVELS)),
+    };
+    value.parse()
+        .map(Some)
+        .map_err(|_| format!("unknown log level '{}', expected {}", value, LEVELS))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn takes_level_in_either_form() {
+        let mut given = args(&["--log-level", "debug", "list"]);
+        assert_eq!(take_level_arg(&mut given), Ok(Some(LevelFilter::Debug)));
+        assert_eq!(given, args(&["list"]));
+
+        let mut given = args(&["list", "--log-level=off"]);
+        assert_eq!(take_level_arg(&mut given), Ok(Some(LevelFilter::Off)));
+        assert_eq!(given, args(&["list"]));
+    }
+
+    #[test]
+    fn leaves_the_run_command_alone() {
+        let mut given = args(&["run", "--", "app", "--log-level", "debug"]);
+        assert_eq!(take_level_arg(&mut given), Ok(None));
+        assert_eq!(given.len(), 5);
+    }
+
+    #[test]
+    fn rejects_missing_or_unknown_levels() {
+        assert!(take_level_arg(&mut args(&["--log-level"])).is_err());
+        assert!(take_level_arg(&mut args(&["--log-level", "loud"])).is_err());
+    }
+}
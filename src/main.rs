@@ -1,314 +1,782 @@
 use iced::{
     widget::{
-        button, container, scrollable, text, text_input, Column, Container,
+        button, checkbox, container, pick_list, scrollable, text, text_input, Column, Container,
         Row, Text, image::Handle,
     },
     Application, Color, Command, Element, Length, Settings, Subscription, Theme,
     theme, executor, time::every, window::{self, Position, icon}, Vector,
+    futures::channel::oneshot,
 };
-use sysinfo::{Pid, System, SystemExt, ProcessExt, PidExt};
 use std::collections::HashMap;
-use std::time::Duration;
 use std::thread;
-use std::sync::{Arc, Mutex};
-use windows::Win32::UI::WindowsAndMessaging::{
-    FindWindowW, GetIconInfo, ICONINFO, HICON,
-};
+use std::time::{Duration, Instant};
+#[cfg(windows)]
+use windows::Win32::UI::WindowsAndMessaging::{GetIconInfo, ICONINFO, HICON};
+#[cfg(windows)]
 use windows::Win32::Graphics::Gdi::{
     GetDIBits, BITMAPINFOHEADER, BITMAPINFO, GetDC, ReleaseDC,
     BI_RGB, DIB_RGB_COLORS, RGBQUAD,
 };
+#[cfg(windows)]
 use windows::Win32::UI::Shell::ExtractIconExW;
-use windows::Win32::Foundation::{HWND, HANDLE, CloseHandle};
-use windows::Win32::System::Threading::{OpenProcess, PROCESS_TERMINATE, TerminateProcess, PROCESS_ACCESS_RIGHTS};
+#[cfg(windows)]
+use windows::Win32::Foundation::HWND;
+#[cfg(windows)]
 use windows::core::PCWSTR;
+#[cfg(windows)]
 use std::ffi::OsString;
+#[cfg(windows)]
 use std::os::windows::ffi::OsStrExt;
+#[cfg(windows)]
 use image::{DynamicImage, ImageBuffer, Rgba};
-use enigo::{Enigo, Key, KeyboardControllable};
-use chrono::{Local, DateTime};
-
-// Constants for colors and styling
-const DARK_BG: Color = Color::from_rgb(0.15, 0.15, 0.15);
-const DARK_BG_LIGHTER: Color = Color::from_rgb(0.2, 0.2, 0.2);
-const DARK_TEXT: Color = Color::from_rgb(0.9, 0.9, 0.9);
-const DARK_SECONDARY_TEXT: Color = Color::from_rgb(0.7, 0.7, 0.7);
-const BORDER_COLOR: Color = Color::from_rgb(0.3, 0.3, 0.3);
-const ROW_HOVER: Color = Color::from_rgb(0.25, 0.25, 0.25);
-const SUCCESS_COLOR: Color = Color::from_rgb(0.2, 0.8, 0.2);
-const WARNING_COLOR: Color = Color::from_rgb(0.8, 0.3, 0.3);
-const WARNING_COLOR_HOVER: Color = Color::from_rgb(0.9, 0.4, 0.4);
-const ACCENT_COLOR: Color = Color::from_rgb(0.2, 0.5, 0.8);
-const ACCENT_BLUE: Color = Color::from_rgb(0.0, 0.6, 1.0);
-const ACCENT_BLUE_HOVER: Color = Color::from_rgb(0.1, 0.7, 1.0);
-
-// Application categories and their executable names
-const OFFICE_APPS: &[&str] = &[
-    "WINWORD.EXE",      // Microsoft Word
-    "EXCEL.EXE",        // Microsoft Excel
-    "POWERPNT.EXE",     // Microsoft PowerPoint
-    "ONENOTE.EXE",      // Microsoft OneNote
-    "OUTLOOK.EXE",      // Microsoft Outlook
-    "PUBLISHER.EXE",    // Microsoft Publisher
-    "MSACCESS.EXE",     // Microsoft Access
-    "swriter.exe",      // LibreOffice Writer
-    "scalc.exe",        // LibreOffice Calc
-    "simpress.exe",     // LibreOffice Impress
-];
-
-const TEXT_EDITORS: &[&str] = &[
-    "notepad.exe",      // Notepad
-    "notepad++.exe",    // Notepad++
-    "sublime_text.exe", // Sublime Text
-    "Code.exe",         // VS Code
-    "atom.exe",         // Atom
-    "vim.exe",          // Vim
-    "gvim.exe",         // GVim
-    "emacs.exe",        // Emacs
-    "wordpad.exe",      // WordPad
-];
-
-const IDES: &[&str] = &[
-    "devenv.exe",       // Visual Studio
-    "idea64.exe",       // IntelliJ IDEA
-    "pycharm64.exe",    // PyCharm
-    "webstorm64.exe",   // WebStorm
-    "rider64.exe",      // Rider
-    "eclipse.exe",      // Eclipse
-    "android studio.exe", // Android Studio
-    "netbeans64.exe",   // NetBeans
-];
-
-const DESIGN_APPS: &[&str] = &[
-    "photoshop.exe",    // Adobe Photoshop
-    "illustrator.exe",  // Adobe Illustrator
-    "gimp-2.10.exe",    // GIMP
-    "inkscape.exe",     // Inkscape
-    "figma.exe",        // Figma
-    "xd.exe",           // Adobe XD
-    "krita.exe",        // Krita
-    "paint.net.exe",    // Paint.NET
-    "designer.exe",     // Qt Designer
-];
-
-const DEVELOPMENT_TOOLS: &[&str] = &[
-    "ssms.exe",         // SQL Server Management Studio
-    "pgadmin4.exe",     // pgAdmin
-    "dbeaver.exe",      // DBeaver
-    "postman.exe",      // Postman
-    "insomnia.exe",     // Insomnia
-    "sourcetree.exe",   // SourceTree
-    "github desktop.exe", // GitHub Desktop
-];
-
-const CREATIVE_TOOLS: &[&str] = &[
-    "premiere.exe",     // Adobe Premiere
-    "aftereffects.exe", // Adobe After Effects
-    "audition.exe",     // Adobe Audition
-    "vegas.exe",        // Vegas Pro
-    "resolve.exe",      // DaVinci Resolve
-    "blender.exe",      // Blender
-    "maya.exe",         // Maya
-    "3dsmax.exe",       // 3ds Max
-];
-
-const BROWSER_APPS: &[&str] = &[
-    "chrome.exe",
-    "firefox.exe",
-    "msedge.exe",
-    "opera.exe",
-    "brave.exe",
-];
-
-const PRODUCTIVITY_APPS: &[&str] = &[
-    "winword.exe",
-    "excel.exe",
-    "powerpnt.exe",
-    "onenote.exe",
-    "outlook.exe",
-    "publisher.exe",
-    "msaccess.exe",
-    "notepad.exe",
-    "notepad++.exe",
-    "code.exe",
-    "sublime_text.exe",
-    "atom.exe",
-];
-
-const DEVELOPMENT_APPS: &[&str] = &[
-    "devenv.exe",
-    "idea64.exe",
-    "pycharm64.exe",
-    "webstorm64.exe",
-    "androidstudio64.exe",
-    "eclipse.exe",
-    "netbeans64.exe",
-    "vscode.exe",
-];
-
+use chrono::Local;
+use log::{error, info, LevelFilter};
+
+mod audit;
+mod backend;
+mod cli;
+mod config;
+mod daemon;
+mod error;
+mod focus;
+mod hooks;
+mod http;
+mod logging;
+mod ipc;
+mod launch;
+mod metrics;
+mod palette;
+mod process_control;
+mod protection;
+mod quota;
+mod resource_limits;
+mod rpc;
+mod scheduler;
+mod settings;
+mod smart_save;
+mod task;
+mod watchdog;
+mod websocket;
+mod window_info;
+
+use audit::{Action, AuditEntry, Outcome, SmartSave};
+use backend::Backend;
+use config::Config;
+use daemon::{Request, Response};
+use focus::{FocusSession, CONFIRMATION_PHRASE};
+use process_control::{parse_cpu_list, PriorityClass};
+use quota::budget_key;
+use resource_limits::{format_bytes, parse_limits, ResourceLimits};
+use palette::Palette;
+use scheduler::{RecentlyEnded, Snapshot};
+use settings::{SettingsField, SettingsForm, COLOR_FIELDS};
+use task::{DeadlineAction, ProcessStatus, Task, TimeInterval};
+
+// How long a toast stays up unless dismissed
+const TOAST_DURATION: Duration = Duration::from_secs(6);
+
+// Short-lived result of an action, shown under the process list
 #[derive(Debug, Clone)]
-struct Task {
-    name: String,
-    pid: u32,
-    cpu_usage: f32,
-    memory_usage: u64,
-    icon: Option<ProcessIcon>,
-    deadline: Option<DateTime<Local>>,
-    status: ProcessStatus,
+struct Toast {
+    message: String,
+    is_error: bool,
+    // An ended process the toast offers to relaunch
+    undo: Option<u32>,
+    shown_at: Instant,
 }
 
-impl Task {
-    fn new(name: String, pid: u32, cpu_usage: f32, memory_usage: u64, icon: Option<ProcessIcon>) -> Self {
-        Self {
-            name,
-            pid,
-            cpu_usage,
-            memory_usage,
-            icon,
-            deadline: None,
-            status: ProcessStatus::Running,
-        }
+impl Toast {
+    fn info(message: String) -> Self {
+        Self { message, is_error: false, undo: None, shown_at: Instant::now() }
     }
 
-    fn get_status(&self) -> ProcessStatus {
-        if let Some(deadline) = self.deadline {
-            let now = Local::now();
-            if now > deadline {
-                ProcessStatus::DeadlineReached
-            } else {
-                ProcessStatus::Running
-            }
-        } else {
-            ProcessStatus::Running
-        }
+    fn error(message: String) -> Self {
+        Self { message, is_error: true, undo: None, shown_at: Instant::now() }
     }
+}
 
-    fn format_deadline(&self) -> String {
-        if let Some(deadline) = self.deadline {
-            let now = Local::now();
-            if now > deadline {
-                "Expired".to_string()
-            } else {
-                let remaining = deadline.signed_duration_since(now);
-                let minutes = remaining.num_minutes();
-                let seconds = remaining.num_seconds() % 60;
-                if minutes > 0 {
-                    format!("{}m {}s left", minutes, seconds)
-                } else {
-                    format!("{}s left", seconds)
-                }
-            }
-        } else {
-            "None".to_string()
-        }
-    }
+// Most recent audit log entries shown on the history page
+const HISTORY_LIMIT: usize = 500;
+
+// Everything End Task is about to do, shown to the user before it happens
+#[derive(Debug, Clone)]
+struct TerminationPreview {
+    pid: u32,
+    name: String,
+    children: Vec<(u32, String)>,
+    smart_save: bool,
+    unsaved_hints: Vec<String>,
+    end_children: bool,
+    dont_ask_again: bool,
 }
 
 #[derive(Debug, Clone)]
 pub enum Message {
     TaskSelected(u32),
     TerminateTask(u32),
+    ConfirmTermination,
+    CancelTermination,
+    EndChildrenToggled(bool),
+    DontAskAgainToggled(bool),
     SetDeadline(u32, TimeInterval),
     ClearDeadline(u32),
+    SetDeadlineAction(u32, DeadlineAction),
+    SuspendTask(u32),
+    ResumeTask(u32),
+    SetPriority(u32, PriorityClass),
+    AffinityInput(String),
+    SetAffinity(u32, Vec<usize>),
+    MemoryLimitInput(String),
+    CpuLimitInput(String),
+    SetLimits(u32, ResourceLimits),
+    RememberProcessSettings(bool),
+    OverrideProtection(u32, bool),
+    ToggleProtectedApp(String),
     Tick,
     SearchInput(String),
     CheckDeadlines,
     CustomDeadlineInput(String),
-}
-
-#[derive(Debug, Clone)]
-pub enum TimeInterval {
-    ThirtyMinutes,
-    OneHour,
-    TwoHours,
-    Custom(DateTime<Local>),
-}
-
-#[derive(Debug, Clone, PartialEq)]
-pub enum ProcessStatus {
-    Running,
-    Terminated,
-    DeadlineReached,
+    BudgetInput(String),
+    SetDailyBudget(String, u32),
+    ClearDailyBudget(String),
+    FocusMinutesInput(String),
+    FocusBlockBrowsers(bool),
+    FocusBlockGames(bool),
+    FocusRequireConfirmation(bool),
+    FocusSuspendInstead(bool),
+    StartFocus(u32),
+    FocusConfirmInput(String),
+    EndFocus,
+    Relaunch(u32),
+    LaunchCommandInput(String),
+    LaunchLimitInput(String),
+    Launch,
+    ShowHistory,
+    CloseHistory,
+    ShowSettings,
+    CloseSettings,
+    SettingsInput(SettingsField, String),
+    SettingsSmartSaveToggled(String, bool),
+    SettingsBlockUnverifiedToggled(bool),
+    SaveSettings,
+    DismissToast,
+    // Whether each before-hook of an End Task vetoed, in the order the processes are ended
+    HooksFinished(Vec<(u32, Option<String>)>),
 }
 
 pub struct TaskManager {
-    system: System,
+    backend: Backend,
     tasks: HashMap<u32, Task>,
+    // Icons are extracted once per executable
+    icons: HashMap<String, Option<ProcessIcon>>,
+    config: Config,
+    palette: Palette,
+    focus: Option<FocusSession>,
     selected_task: Option<u32>,
     search_query: String,
     custom_deadline: String,
+    budget_input: String,
+    focus_minutes: String,
+    focus_block_browsers: bool,
+    focus_block_games: bool,
+    focus_require_confirmation: bool,
+    focus_suspend_instead: bool,
+    focus_confirm_input: String,
+    launch_command: String,
+    launch_limit: String,
+    // Processes started from the launch panel whose exit we still have to report
+    launched: Vec<(u32, String)>,
+    recently_ended: Vec<RecentlyEnded>,
+    affinity_input: String,
+    memory_limit_input: String,
+    cpu_limit_input: String,
+    remember_process_settings: bool,
+    pending_termination: Option<TerminationPreview>,
+    // Audit log entries while the history page is open
+    history: Option<Vec<AuditEntry>>,
+    // Unsaved edits while the settings page is open
+    settings: Option<SettingsForm>,
+    toast: Option<Toast>,
 }
 
 impl TaskManager {
-    fn update_tasks(&mut self) {
-        self.system.refresh_all();
-        
-        let mut updated_tasks = HashMap::new();
-        for process in self.system.processes().values() {
-            let pid = process.pid().as_u32();
-            let name = process.name().to_string();
-            
-            // Get CPU usage with proper refresh
-            let cpu_usage = process.cpu_usage();
-            let memory_usage = process.memory();
-
-            // Filter based on search query
-            if !self.search_query.is_empty() {
-                if !name.to_lowercase().contains(&self.search_query.to_lowercase()) {
-                    continue;
+    fn refresh(&mut self) {
+        if let Some(snapshot) = self.backend.refresh() {
+            self.apply_snapshot(snapshot);
+        }
+    }
+
+    fn apply_snapshot(&mut self, snapshot: Snapshot) {
+        let icons = &mut self.icons;
+        self.tasks = snapshot.tasks
+            .into_iter()
+            .map(|mut task| {
+                task.icon = icons
+                    .entry(task.exe.clone())
+                    .or_insert_with(|| ProcessIcon::from_exe_path(&task.exe))
+                    .clone();
+                (task.pid, task)
+            })
+            .collect();
+        let tasks = &self.tasks;
+        self.icons.retain(|exe, _| tasks.values().any(|task| &task.exe == exe));
+        self.report_exits();
+
+        if snapshot.focus.is_none() {
+            self.focus_confirm_input.clear();
+        }
+        self.focus = snapshot.focus;
+        self.recently_ended = snapshot.recently_ended;
+        if snapshot.config.colors != self.config.colors {
+            self.palette = Palette::from_config(&snapshot.config.colors);
+        }
+        self.config = snapshot.config;
+    }
+
+    // Sends a command to the scheduler and shows its effect right away
+    fn send(&mut self, request: Request) -> bool {
+        let response = self.backend.request(request);
+        self.show_response(response)
+    }
+
+    fn show_response(&mut self, response: Response) -> bool {
+        let succeeded = match response {
+            Response::Error(e) => {
+                error!("{}", e);
+                self.toast = Some(Toast::error(e.to_string()));
+                false
+            }
+            _ => true,
+        };
+        if let Response::Snapshot(snapshot) = self.backend.request(Request::Snapshot) {
+            self.apply_snapshot(*snapshot);
+        }
+        succeeded
+    }
+
+    fn launch(&mut self) {
+        let Some(command) = launch::split_command(&self.launch_command).filter(|command| !command.is_empty()) else {
+            self.toast = Some(Toast::error("Enter a command to launch, with quotes closed".to_string()));
+            return;
+        };
+        let deadline = match self.launch_limit.trim() {
+            "" => None,
+            limit => match task::parse_duration(limit) {
+                Some(duration) => Some(Local::now() + duration),
+                None => {
+                    self.toast = Some(Toast::error(format!("'{}' is not a time limit like 30m or 2h", limit)));
+                    return;
                 }
+            },
+        };
+        let name = launch::program_name(&command);
+        match self.backend.request(Request::Launch { command, limit: deadline.map(TimeInterval::Custom) }) {
+            Response::Launched(pid) => {
+                self.toast = Some(Toast::info(match deadline {
+                    Some(deadline) => format!("Launched {} (PID {}) until {}", name, pid, deadline.format("%H:%M")),
+                    None => format!("Launched {} (PID {})", name, pid),
+                }));
+                self.launched.push((pid, name));
+                self.launch_command.clear();
+            }
+            Response::Error(e) => {
+                error!("{}", e);
+                self.toast = Some(Toast::error(e.to_string()));
             }
+            _ => {}
+        }
+        self.refresh();
+    }
 
-            // Get process icon
-            let icon = if let Some(exe_path) = process.exe().to_str() {
-                ProcessIcon::from_exe_path(exe_path)
-            } else {
-                None
-            };
+    fn relaunch(&mut self, pid: u32) {
+        let name = self.recently_ended
+            .iter()
+            .find(|ended| ended.pid == pid)
+            .map(|ended| ended.name.clone())
+            .unwrap_or_default();
+        match self.backend.request(Request::Relaunch(pid)) {
+            Response::Launched(new_pid) => {
+                self.toast = Some(Toast::info(format!("Relaunched {} (PID {})", name, new_pid)));
+                self.launched.push((new_pid, name));
+            }
+            Response::Error(e) => {
+                error!("{}", e);
+                self.toast = Some(Toast::error(e.to_string()));
+            }
+            _ => {}
+        }
+        self.refresh();
+    }
 
-            // Update or create task
-            if let Some(existing_task) = self.tasks.get(&pid) {
-                updated_tasks.insert(pid, Task {
-                    name: existing_task.name.clone(),
-                    pid,
-                    cpu_usage,
-                    memory_usage,
-                    icon: icon.or_else(|| existing_task.icon.clone()),
-                    deadline: existing_task.deadline,
-                    status: existing_task.get_status(),
-                });
-            } else {
-                updated_tasks.insert(pid, Task::new(name, pid, cpu_usage, memory_usage, icon));
+    // Tells the user how launched processes that are gone from the list ended
+    fn report_exits(&mut self) {
+        let tasks = &self.tasks;
+        let (running, exited): (Vec<_>, Vec<_>) = self.launched
+            .drain(..)
+            .partition(|(pid, _)| tasks.contains_key(pid));
+        self.launched = running;
+        if exited.is_empty() {
+            return;
+        }
+        // The scheduler records the exit status before the process leaves the list
+        let history = audit::recent(HISTORY_LIMIT);
+        for (pid, name) in exited {
+            let outcome = history.iter()
+                .find(|entry| entry.pid == pid && entry.action == Action::Launch)
+                .map(|entry| entry.outcome.clone());
+            self.toast = Some(match outcome {
+                Some(Outcome::Succeeded) => Toast::info(format!("{} (PID {}) exited normally", name, pid)),
+                Some(Outcome::Failed { error }) | Some(Outcome::Refused { reason: error }) => {
+                    Toast::error(format!("{} (PID {}) {}", name, pid, error))
+                }
+                None => Toast::info(format!("{} (PID {}) has exited", name, pid)),
+            });
+        }
+    }
+
+    // Children first, so none of them is orphaned and reparented before we get to it
+    fn end_task(&mut self, pid: u32, children: &[(u32, String)]) -> Command<Message> {
+        let pids: Vec<u32> = children.iter().map(|&(child_pid, _)| child_pid).chain([pid]).collect();
+        let hooks: Vec<_> = pids
+            .iter()
+            .filter_map(|&pid| self.backend.before_hooks(pid).map(|hooks| (pid, hooks)))
+            .collect();
+        if hooks.iter().all(|(_, hooks)| hooks.is_empty()) {
+            for pid in pids {
+                self.terminate_process(pid, None);
+            }
+            return Command::none();
+        }
+        // Hooks can take as long as their timeout, which would freeze the window
+        let (sender, receiver) = oneshot::channel();
+        thread::spawn(move || {
+            let verdicts = hooks.into_iter().map(|(pid, hooks)| (pid, hooks.run())).collect();
+            let _ = sender.send(verdicts);
+        });
+        Command::perform(receiver, |verdicts| Message::HooksFinished(verdicts.unwrap_or_default()))
+    }
+
+    fn terminate_process(&mut self, pid: u32, vetoed_by: Option<String>) {
+        let name = self.tasks.get(&pid).map(|task| task.name.clone()).unwrap_or_default();
+        let response = self.backend.terminate(pid, vetoed_by);
+        if self.show_response(response) {
+            let mut toast = Toast::info(format!("Ended {} (PID {})", name, pid));
+            if self.recently_ended.iter().any(|ended| ended.pid == pid) {
+                toast.undo = Some(pid);
+            }
+            self.toast = Some(toast);
+            if self.selected_task == Some(pid) {
+                self.selected_task = None;
             }
         }
+    }
+
+    fn view_toast(&self, toast: &Toast) -> Element<'_, Message> {
+        let palette = self.palette;
+        let mut row = Row::new()
+            .spacing(10)
+            .push(text(&toast.message).size(14).width(Length::Fill));
+        if let Some(pid) = toast.undo {
+            row = row.push(
+                button(Text::new("Undo").size(12))
+                    .on_press(Message::Relaunch(pid))
+                    .style(theme::Button::Custom(Box::new(CustomButtonStyle {
+                        background: palette.accent,
+                        hover_background: palette.accent_hover,
+                        text_color: Color::WHITE,
+                        border_radius: 6.0,
+                        border_width: 0.0,
+                        border_color: Color::TRANSPARENT,
+                    })))
+                    .padding(4)
+            );
+        }
+        Container::new(
+            row
+                .push(
+                    button(Text::new("Dismiss").size(12))
+                        .on_press(Message::DismissToast)
+                        .style(theme::Button::Custom(Box::new(CustomButtonStyle {
+                            background: Color::TRANSPARENT,
+                            hover_background: palette.row_hover,
+                            text_color: palette.text,
+                            border_radius: 6.0,
+                            border_width: 1.0,
+                            border_color: palette.border,
+                        })))
+                        .padding(4)
+                )
+        )
+        .padding(10)
+        .width(Length::Fill)
+        .style(theme::Container::Custom(Box::new(CustomStyle {
+            background: if toast.is_error { palette.warning } else { palette.surface },
+            text: if toast.is_error { Color::WHITE } else { palette.text },
+            border_radius: 8.0,
+            border_width: 1.0,
+            border_color: palette.border,
+        })))
+        .into()
+    }
 
-        // Replace tasks with filtered and updated list
-        self.tasks = updated_tasks;
+    fn matches_search(&self, task: &Task) -> bool {
+        self.search_query.is_empty()
+            || task.name.to_lowercase().contains(&self.search_query.to_lowercase())
+    }
 
-        // Check for deadline reached
-        let now = Local::now();
-        let mut to_terminate = Vec::new();
-        for (&pid, task) in &self.tasks {
-            if let Some(deadline) = task.deadline {
-                if now > deadline {
-                    to_terminate.push(pid);
-                }
+    fn preview_termination(&self, pid: u32) -> Option<TerminationPreview> {
+        let task = self.tasks.get(&pid)?;
+        Some(TerminationPreview {
+            pid,
+            children: task::descendants(self.tasks.values(), pid),
+            smart_save: smart_save::should_try_save(&task.name, &self.config),
+            unsaved_hints: window_info::unsaved_work_hints(pid),
+            end_children: true,
+            dont_ask_again: false,
+            name: task.name.clone(),
+        })
+    }
+    fn view_termination_preview(&self, preview: &TerminationPreview) -> Element<'_, Message> {
+        let palette = self.palette;
+        let mut details = Column::new()
+            .spacing(10)
+            .push(text(format!("End {} (PID {})?", preview.name, preview.pid)).size(22));
+
+        details = details.push(text(if preview.smart_save {
+            "💾 Smart save will try to save open work before the process is ended."
+        } else {
+            "Smart save does not apply to this application."
+        }).size(14));
+
+        if preview.unsaved_hints.is_empty() {
+            details = details.push(text("No unsaved work detected in window titles.").size(14).style(palette.secondary_text));
+        } else {
+            details = details.push(text("⚠️ These windows look like they have unsaved changes:").size(14).style(palette.warning_hover));
+            for title in &preview.unsaved_hints {
+                details = details.push(text(format!("    {}", title)).size(14));
             }
         }
 
-        // Terminate tasks that reached their deadline
-        for pid in to_terminate {
-            self.terminate_process(pid);
+        if preview.children.is_empty() {
+            details = details.push(text("No child processes.").size(14).style(palette.secondary_text));
+        } else {
+            details = details.push(
+                checkbox(
+                    format!("Also end {} child process(es)", preview.children.len()),
+                    preview.end_children,
+                    Message::EndChildrenToggled,
+                )
+                .size(16)
+            );
+            let mut children = Column::new().spacing(2);
+            for (pid, name) in &preview.children {
+                children = children.push(text(format!("    {} (PID {})", name, pid)).size(13).style(palette.secondary_text));
+            }
+            details = details.push(scrollable(children).height(Length::Fixed(120.0)));
         }
+
+        details = details
+            .push(
+                checkbox(
+                    format!("Don't ask again for {}", preview.name),
+                    preview.dont_ask_again,
+                    Message::DontAskAgainToggled,
+                )
+                .size(16)
+            )
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .push(
+                        button(Text::new("End Task").size(14))
+                            .on_press(Message::ConfirmTermination)
+                            .style(theme::Button::Custom(Box::new(CustomButtonStyle {
+                                background: palette.warning,
+                                hover_background: palette.warning_hover,
+                                text_color: Color::WHITE,
+                                border_radius: 6.0,
+                                border_width: 0.0,
+                                border_color: Color::TRANSPARENT,
+                            })))
+                            .padding(8)
+                    )
+                    .push(
+                        button(Text::new("Cancel").size(14))
+                            .on_press(Message::CancelTermination)
+                            .style(theme::Button::Custom(Box::new(CustomButtonStyle {
+                                background: palette.surface,
+                                hover_background: palette.row_hover,
+                                text_color: palette.text,
+                                border_radius: 6.0,
+                                border_width: 1.0,
+                                border_color: palette.border,
+                            })))
+                            .padding(8)
+                    )
+            );
+
+        let dialog = Container::new(details)
+            .padding(20)
+            .max_width(600)
+            .style(theme::Container::Custom(Box::new(CustomStyle {
+                background: palette.surface,
+                text: palette.text,
+                border_radius: 8.0,
+                border_width: 1.0,
+                border_color: palette.border,
+            })));
+
+        Container::new(dialog)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .center_x()
+            .center_y()
+            .padding(20)
+            .style(theme::Container::Custom(Box::new(CustomStyle {
+                background: palette.background,
+                text: palette.text,
+                border_radius: 12.0,
+                border_width: 1.0,
+                border_color: palette.border,
+            })))
+            .into()
     }
 
-    fn view(&self) -> Element<Message> {
-        let total_cpu: f32 = self.tasks.values().map(|t| t.cpu_usage).sum();
-        let total_memory_mb: f32 = self.tasks.values().map(|t| t.memory_usage as f32).sum();
-        let _system_memory_mb = self.system.total_memory() as f32;
+    fn view_settings(&self, form: &SettingsForm) -> Element<'_, Message> {
+        let palette = self.palette;
+        let button_style = || theme::Button::Custom(Box::new(CustomButtonStyle {
+            background: palette.surface,
+            hover_background: palette.row_hover,
+            text_color: palette.text,
+            border_radius: 6.0,
+            border_width: 1.0,
+            border_color: palette.border,
+        }));
+        let section_style = || theme::Container::Custom(Box::new(CustomStyle {
+            background: palette.surface,
+            text: palette.text,
+            border_radius: 8.0,
+            border_width: 1.0,
+            border_color: palette.border,
+        }));
+        let field = |label: String, placeholder: &str, field: SettingsField| {
+            Row::new()
+                .spacing(10)
+                .push(text(label).size(14).width(Length::Fixed(220.0)))
+                .push(
+                    text_input(placeholder, form.value(&field))
+                        .on_input(move |value| Message::SettingsInput(field.clone(), value))
+                        .padding(6)
+                        .size(14)
+                )
+        };
+
+        let header = Row::new()
+            .spacing(10)
+            .push(text("Settings").size(28).width(Length::Fill))
+            .push(
+                button(Text::new("Cancel").size(14))
+                    .on_press(Message::CloseSettings)
+                    .style(button_style())
+                    .padding(8)
+            )
+            .push(
+                button(Text::new("Save").size(14))
+                    .on_press(Message::SaveSettings)
+                    .style(theme::Button::Custom(Box::new(CustomButtonStyle {
+                        background: palette.accent,
+                        hover_background: palette.accent_hover,
+                        text_color: Color::WHITE,
+                        border_radius: 6.0,
+                        border_width: 0.0,
+                        border_color: Color::TRANSPARENT,
+                    })))
+                    .padding(8)
+            );
+
+        let general = Column::new()
+            .spacing(10)
+            .push(text("General").size(18))
+            .push(field("Refresh interval (ms)".to_string(), "1000", SettingsField::RefreshInterval))
+            .push(field("Deadline buttons (minutes)".to_string(), "30, 60, 120", SettingsField::DeadlinePresets))
+            .push(field("Memory limit buttons (MB)".to_string(), "1024, 4096", SettingsField::MemoryLimitPresets));
 
-        // Create a sorted list of tasks that keeps selected task in place
-        let mut sorted_tasks: Vec<(&u32, &Task)> = self.tasks.iter().collect();
+        let mut smart_save = Column::new()
+            .spacing(10)
+            .push(text("Smart Save").size(18))
+            .push(field("Wait after saving (ms)".to_string(), "2000", SettingsField::WaitAfterSave))
+            .push(field("Wait before ending (ms)".to_string(), "1000", SettingsField::WaitBeforeTerminate))
+            .push(
+                checkbox(
+                    "Don't end apps unless the save is confirmed",
+                    form.block_unverified,
+                    Message::SettingsBlockUnverifiedToggled,
+                )
+                .size(16)
+            )
+            .push(text("Try to save these apps first (profiles are edited in config.json):").size(14).style(palette.secondary_text));
+        for profile in &self.config.smart_save.profiles {
+            let enabled = form.smart_save_profiles.contains(&profile.name);
+            let toggled = profile.name.clone();
+            smart_save = smart_save.push(
+                checkbox(profile.name.as_str(), enabled, move |enabled| {
+                    Message::SettingsSmartSaveToggled(toggled.clone(), enabled)
+                })
+                .size(16)
+            );
+        }
+
+        let mut colors = Column::new()
+            .spacing(10)
+            .push(text("Colors").size(18));
+        for name in COLOR_FIELDS {
+            colors = colors.push(field(name.to_string(), "#rrggbb", SettingsField::Color(name)));
+        }
+
+        let mut categories = Column::new()
+            .spacing(10)
+            .push(text("App Categories").size(18))
+            .push(text("Executable names, separated by commas").size(14).style(palette.secondary_text));
+        for category in form.app_categories.keys() {
+            categories = categories.push(field(category.clone(), "app.exe, other.exe", SettingsField::Category(category.clone())));
+        }
+
+        let sections = [general, smart_save, colors, categories]
+            .into_iter()
+            .fold(Column::new().spacing(15), |column, section| {
+                column.push(Container::new(section).padding(15).width(Length::Fill).style(section_style()))
+            });
+
+        let mut content = Column::new()
+            .spacing(20)
+            .push(header)
+            .push(scrollable(sections).height(Length::Fill));
+        if let Some(toast) = &self.toast {
+            content = content.push(self.view_toast(toast));
+        }
+
+        Container::new(content)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .padding(20)
+            .style(theme::Container::Custom(Box::new(CustomStyle {
+                background: palette.background,
+                text: palette.text,
+                border_radius: 12.0,
+                border_width: 1.0,
+                border_color: palette.border,
+            })))
+            .into()
+    }
+
+    fn view_history(&self, history: &[AuditEntry]) -> Element<'_, Message> {
+        let palette = self.palette;
+        let header = Row::new()
+            .spacing(20)
+            .push(text("History").size(28).width(Length::Fill))
+            .push(
+                button(Text::new("Back").size(14))
+                    .on_press(Message::CloseHistory)
+                    .style(theme::Button::Custom(Box::new(CustomButtonStyle {
+                        background: palette.surface,
+                        hover_background: palette.row_hover,
+                        text_color: palette.text,
+                        border_radius: 6.0,
+                        border_width: 1.0,
+                        border_color: palette.border,
+                    })))
+                    .padding(8)
+            );
+
+        let table_header = Row::new()
+            .spacing(10)
+            .push(text("Time").width(Length::Fixed(150.0)))
+            .push(text("Process").width(Length::FillPortion(3)))
+            .push(text("Action").width(Length::Fixed(110.0)))
+            .push(text("Trigger").width(Length::Fixed(110.0)))
+            .push(text("Smart save").width(Length::Fixed(100.0)))
+            .push(text("Outcome").width(Length::FillPortion(3)));
+
+        let mut rows = Column::new().spacing(2);
+        if history.is_empty() {
+            rows = rows.push(text("Nothing has been ended or suspended yet.").size(14).style(palette.secondary_text));
+        }
+        for entry in history {
+            let outcome_color = match entry.outcome {
+                Outcome::Succeeded => palette.success,
+                _ => palette.warning_hover,
+            };
+            let smart_save = match entry.smart_save {
+                SmartSave::NotAttempted => "—",
+                SmartSave::Saved => "Saved",
+                SmartSave::Unsaved => "Unsaved",
+                SmartSave::Unknown => "Unknown",
+                SmartSave::Failed => "Failed",
+            };
+            rows = rows.push(
+                Container::new(
+                    Row::new()
+                        .spacing(10)
+                        .push(text(entry.started.format("%Y-%m-%d %H:%M:%S").to_string()).size(14).width(Length::Fixed(150.0)))
+                        .push(text(format!("{} (PID {})", entry.name, entry.pid)).size(14).width(Length::FillPortion(3)))
+                        .push(text(entry.action.to_string()).size(14).width(Length::Fixed(110.0)))
+                        .push(text(entry.trigger.label()).size(14).width(Length::Fixed(110.0)))
+                        .push(text(smart_save).size(14).width(Length::Fixed(100.0)))
+                        .push(text(entry.outcome.label()).size(14).width(Length::FillPortion(3)).style(outcome_color))
+                )
+                .padding(8)
+            );
+        }
+
+        let content = Column::new()
+            .spacing(20)
+            .push(header)
+            .push(
+                Container::new(table_header)
+                    .padding(10)
+                    .style(theme::Container::Custom(Box::new(CustomStyle {
+                        background: Color { a: 0.1, ..palette.background },
+                        text: palette.text,
+                        border_radius: 6.0,
+                        border_width: 1.0,
+                        border_color: palette.border,
+                    })))
+            )
+            .push(scrollable(rows).height(Length::Fill));
+
+        Container::new(content)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .padding(20)
+            .style(theme::Container::Custom(Box::new(CustomStyle {
+                background: palette.background,
+                text: palette.text,
+                border_radius: 12.0,
+                border_width: 1.0,
+                border_color: palette.border,
+            })))
+            .into()
+    }
+
+    fn view(&self) -> Element<'_, Message> {
+        if let Some(preview) = &self.pending_termination {
+            return self.view_termination_preview(preview);
+        }
+        if let Some(history) = &self.history {
+            return self.view_history(history);
+        }
+        if let Some(form) = &self.settings {
+            return self.view_settings(form);
+        }
+        let palette = self.palette;
+
+        // Create a sorted list of matching tasks that keeps selected task in place
+        let mut sorted_tasks: Vec<(&u32, &Task)> = self.tasks
+            .iter()
+            .filter(|(_, task)| self.matches_search(task))
+            .collect();
+        let total_cpu: f32 = sorted_tasks.iter().map(|(_, t)| t.cpu_usage).sum();
+        let total_memory_mb: f32 = sorted_tasks.iter().map(|(_, t)| t.memory_usage as f32).sum();
         
         if let Some(selected_pid) = self.selected_task {
             // First find the selected task's position
@@ -339,21 +807,51 @@ impl TaskManager {
         let header = Container::new(
             Column::new()
                 .spacing(5)
-                .push(text("Task Manager").size(28))
+                .push(
+                    Row::new()
+                        .spacing(10)
+                        .push(text("Task Manager").size(28).width(Length::Fill))
+                        .push(
+                            button(Text::new("History").size(14))
+                                .on_press(Message::ShowHistory)
+                                .style(theme::Button::Custom(Box::new(CustomButtonStyle {
+                                    background: palette.surface,
+                                    hover_background: palette.row_hover,
+                                    text_color: palette.text,
+                                    border_radius: 6.0,
+                                    border_width: 1.0,
+                                    border_color: palette.border,
+                                })))
+                                .padding(8)
+                        )
+                        .push(
+                            button(Text::new("Settings").size(14))
+                                .on_press(Message::ShowSettings)
+                                .style(theme::Button::Custom(Box::new(CustomButtonStyle {
+                                    background: palette.surface,
+                                    hover_background: palette.row_hover,
+                                    text_color: palette.text,
+                                    border_radius: 6.0,
+                                    border_width: 1.0,
+                                    border_color: palette.border,
+                                })))
+                                .padding(8)
+                        )
+                )
                 .push(
                     Row::new()
                         .spacing(20)
-                        .push(text(&format!("Total Tasks: {}", self.tasks.len())).size(16))
-                        .push(text(&format!("CPU Usage: {:.1}%", total_cpu)).size(16))
-                        .push(text(&format!("Memory Usage: {:.1} GB", total_memory_mb / 1024.0 / 1024.0)).size(16))
+                        .push(text(format!("Total Tasks: {}", sorted_tasks.len())).size(16))
+                        .push(text(format!("CPU Usage: {:.1}%", total_cpu)).size(16))
+                        .push(text(format!("Memory Usage: {:.1} GB", total_memory_mb / 1024.0 / 1024.0)).size(16))
                 )
         )
         .style(theme::Container::Custom(Box::new(CustomStyle {
-            background: DARK_BG,
-            text: DARK_TEXT,
+            background: palette.background,
+            text: palette.text,
             border_radius: 12.0,
             border_width: 1.0,
-            border_color: BORDER_COLOR,
+            border_color: palette.border,
         })))
         .padding(20)
         .width(Length::Fill);
@@ -365,48 +863,416 @@ impl TaskManager {
                 .size(16)
         )
         .style(theme::Container::Custom(Box::new(CustomStyle {
-            background: DARK_BG_LIGHTER,
-            text: DARK_TEXT,
+            background: palette.surface,
+            text: palette.text,
             border_radius: 8.0,
             border_width: 1.0,
-            border_color: BORDER_COLOR,
+            border_color: palette.border,
         })))
         .padding(10)
         .width(Length::Fill);
 
+        let focus_panel = if let Some(session) = &self.focus {
+            let mut end_button = button(Text::new("End Session").size(14))
+                .style(theme::Button::Custom(Box::new(CustomButtonStyle {
+                    background: palette.warning,
+                    hover_background: palette.warning_hover,
+                    text_color: Color::WHITE,
+                    border_radius: 6.0,
+                    border_width: 0.0,
+                    border_color: Color::TRANSPARENT,
+                })))
+                .padding(8);
+            if session.can_end_early(&self.focus_confirm_input) {
+                end_button = end_button.on_press(Message::EndFocus);
+            }
+
+            let mut row = Row::new()
+                .spacing(10)
+                .push(
+                    text(format!("🎯 Focus session: {}", session.format_remaining()))
+                        .size(16)
+                        .style(palette.success)
+                );
+            if session.require_confirmation {
+                row = row.push(
+                    text_input(&format!("Type {} to end early", CONFIRMATION_PHRASE), &self.focus_confirm_input)
+                        .on_input(Message::FocusConfirmInput)
+                        .padding(8)
+                        .size(14)
+                );
+            }
+            Container::new(row.push(end_button))
+        } else {
+            let focus_minutes = task::parse_minutes(&self.focus_minutes);
+            let mut row = Row::new()
+                .spacing(10)
+                .push(Text::new("Focus:").size(14))
+                .push(
+                    text_input("Minutes", &self.focus_minutes)
+                        .on_input(Message::FocusMinutesInput)
+                        .padding(8)
+                        .size(14)
+                        .width(Length::Fixed(90.0))
+                );
+            if focus_minutes.is_none() && !self.focus_minutes.trim().is_empty() {
+                row = row.push(text("Enter whole minutes").size(14).style(palette.warning_hover));
+            }
+            Container::new(
+                row
+                    .push(checkbox("Browsers", self.focus_block_browsers, Message::FocusBlockBrowsers).size(16))
+                    .push(checkbox("Games", self.focus_block_games, Message::FocusBlockGames).size(16))
+                    .push(checkbox("Confirm to end early", self.focus_require_confirmation, Message::FocusRequireConfirmation).size(16))
+                    .push(checkbox("Suspend instead of ending", self.focus_suspend_instead, Message::FocusSuspendInstead).size(16))
+                    .push(
+                        button(Text::new("Start Focus").size(14))
+                            .on_press_maybe(focus_minutes.map(Message::StartFocus))
+                            .style(theme::Button::Custom(Box::new(CustomButtonStyle {
+                                background: palette.accent,
+                                hover_background: palette.accent_hover,
+                                text_color: Color::WHITE,
+                                border_radius: 6.0,
+                                border_width: 0.0,
+                                border_color: Color::TRANSPARENT,
+                            })))
+                            .padding(8)
+                    )
+            )
+        }
+        .padding(10)
+        .width(Length::Fill)
+        .style(theme::Container::Custom(Box::new(CustomStyle {
+            background: palette.surface,
+            text: palette.text,
+            border_radius: 8.0,
+            border_width: 1.0,
+            border_color: palette.border,
+        })));
+
+        let mut launch_column = Column::new().spacing(10).push(
+            Row::new()
+                .spacing(10)
+                .push(Text::new("Launch:").size(14))
+                .push(
+                    text_input("Command and arguments", &self.launch_command)
+                        .on_input(Message::LaunchCommandInput)
+                        .on_submit(Message::Launch)
+                        .padding(8)
+                        .size(14)
+                )
+                .push(
+                    text_input("Limit, e.g. 2h", &self.launch_limit)
+                        .on_input(Message::LaunchLimitInput)
+                        .on_submit(Message::Launch)
+                        .padding(8)
+                        .size(14)
+                        .width(Length::Fixed(120.0))
+                )
+                .push(
+                    button(Text::new("Launch").size(14))
+                        .on_press(Message::Launch)
+                        .style(theme::Button::Custom(Box::new(CustomButtonStyle {
+                            background: palette.accent,
+                            hover_background: palette.accent_hover,
+                            text_color: Color::WHITE,
+                            border_radius: 6.0,
+                            border_width: 0.0,
+                            border_color: Color::TRANSPARENT,
+                        })))
+                        .padding(8)
+                )
+        );
+        // Processes ended within the relaunch window, newest first
+        for ended in &self.recently_ended {
+            launch_column = launch_column.push(
+                Row::new()
+                    .spacing(10)
+                    .push(
+                        text(format!("{} (PID {}) ended at {}", ended.name, ended.pid, ended.ended_at.format("%H:%M")))
+                            .size(14)
+                            .width(Length::Fill)
+                    )
+                    .push(
+                        button(Text::new("Relaunch").size(14))
+                            .on_press(Message::Relaunch(ended.pid))
+                            .style(theme::Button::Custom(Box::new(CustomButtonStyle {
+                                background: palette.accent_muted,
+                                hover_background: palette.accent,
+                                text_color: Color::WHITE,
+                                border_radius: 6.0,
+                                border_width: 0.0,
+                                border_color: Color::TRANSPARENT,
+                            })))
+                            .padding(8)
+                    )
+            );
+        }
+        let launch_panel = Container::new(launch_column)
+        .padding(10)
+        .width(Length::Fill)
+        .style(theme::Container::Custom(Box::new(CustomStyle {
+            background: palette.surface,
+            text: palette.text,
+            border_radius: 8.0,
+            border_width: 1.0,
+            border_color: palette.border,
+        })));
+
         let selected_controls = if let Some(selected_pid) = self.selected_task {
+            let selected = self.tasks.get(&selected_pid);
+            let selected_name = selected
+                .map(|task| task.name.clone())
+                .unwrap_or_default();
+            let deadline_action = selected.map(|task| task.deadline_action);
+            let priority = selected.and_then(|task| task.priority);
+            let affinity = parse_cpu_list(&self.affinity_input);
+            let limits = parse_limits(&self.memory_limit_input, &self.cpu_limit_input);
+            let current_limits = selected.map(|task| task.limits).unwrap_or_default();
+            let is_suspended = selected.is_some_and(|task| task.status == ProcessStatus::Suspended);
+            let user_protected = self.config.protected_processes
+                .iter()
+                .any(|protected| protected.eq_ignore_ascii_case(&selected_name));
+
+            let mut protection_row = Row::new().spacing(10);
+            if let Some(protection) = selected.and_then(|task| task.protection) {
+                protection_row = protection_row.push(
+                    text(format!("🛡️ {} is protected: {}", selected_name, protection.reason()))
+                        .size(14)
+                        .style(palette.warning_hover)
+                );
+                if protection.can_override() {
+                    protection_row = protection_row.push(
+                        checkbox(
+                            "Override protection (admin)",
+                            selected.is_some_and(|task| task.protection_overridden),
+                            move |enabled| Message::OverrideProtection(selected_pid, enabled),
+                        )
+                        .size(16)
+                    );
+                }
+            }
+
+            let mut preset_buttons = Row::new().spacing(10);
+            for &minutes in &self.config.deadline_presets {
+                preset_buttons = preset_buttons.push(
+                    button(Text::new(task::format_minutes(minutes)).size(14))
+                        .on_press(Message::SetDeadline(selected_pid, TimeInterval::Minutes(minutes)))
+                        .style(theme::Button::Custom(Box::new(CustomButtonStyle {
+                            background: palette.accent,
+                            hover_background: palette.accent_hover,
+                            text_color: Color::WHITE,
+                            border_radius: 6.0,
+                            border_width: 0.0,
+                            border_color: Color::TRANSPARENT,
+                        })))
+                        .padding(8)
+                );
+            }
+            // Capping memory is the gentler alternative, so it sits right next to the deadlines
+            for &megabytes in &self.config.memory_limit_presets_mb {
+                let memory_bytes = megabytes << 20;
+                preset_buttons = preset_buttons.push(
+                    button(Text::new(format!("Limit to {}", format_bytes(memory_bytes))).size(14))
+                        .on_press(Message::SetLimits(selected_pid, ResourceLimits {
+                            memory_bytes: Some(memory_bytes),
+                            ..current_limits
+                        }))
+                        .style(theme::Button::Custom(Box::new(CustomButtonStyle {
+                            background: palette.accent_muted,
+                            hover_background: palette.accent,
+                            text_color: Color::WHITE,
+                            border_radius: 6.0,
+                            border_width: 0.0,
+                            border_color: Color::TRANSPARENT,
+                        })))
+                        .padding(8)
+                );
+            }
+
             Container::new(
                 Column::new()
                     .spacing(10)
+                    .push(protection_row)
                     .push(
                         Row::new()
                             .spacing(10)
-                            .push(Text::new("Set Deadline:").size(14))
+                            .push(Text::new("Set Deadline:").size(14))
+                            .push(preset_buttons)
+                            .push(
+                                Row::new()
+                                    .spacing(10)
+                                    .push(
+                                        text_input("Minutes", &self.custom_deadline)
+                                            .on_input(Message::CustomDeadlineInput)
+                                            .padding(8)
+                                            .size(14)
+                                    )
+                                    .push(
+                                        button(Text::new("Set Custom").size(14))
+                                            .on_press({
+                                                let minutes = self.custom_deadline.parse::<i64>().unwrap_or(30);
+                                                Message::SetDeadline(
+                                                    selected_pid,
+                                                    TimeInterval::Custom(Local::now() + chrono::Duration::minutes(minutes)),
+                                                )
+                                            })
+                                            .style(theme::Button::Custom(Box::new(CustomButtonStyle {
+                                                background: palette.accent,
+                                                hover_background: palette.accent_hover,
+                                                text_color: Color::WHITE,
+                                                border_radius: 6.0,
+                                                border_width: 0.0,
+                                                border_color: Color::TRANSPARENT,
+                                            })))
+                                            .padding(8)
+                                    )
+                            )
+                            .push(
+                                button(Text::new("Clear").size(14))
+                                    .on_press(Message::ClearDeadline(selected_pid))
+                                    .style(theme::Button::Custom(Box::new(CustomButtonStyle {
+                                        background: palette.warning,
+                                        hover_background: palette.warning_hover,
+                                        text_color: Color::WHITE,
+                                        border_radius: 6.0,
+                                        border_width: 0.0,
+                                        border_color: Color::TRANSPARENT,
+                                    })))
+                                    .padding(8)
+                            )
+                    )
+                    .push(
+                        Row::new()
+                            .spacing(10)
+                            .push(Text::new("At Deadline:").size(14))
+                            .push(
+                                pick_list(&DeadlineAction::ALL[..], deadline_action, move |action| {
+                                    Message::SetDeadlineAction(selected_pid, action)
+                                })
+                                .text_size(14)
+                            )
+                            .push(Text::new("Priority:").size(14))
+                            .push(
+                                pick_list(&PriorityClass::ALL[..], priority, move |priority| {
+                                    Message::SetPriority(selected_pid, priority)
+                                })
+                                .placeholder("Unchanged")
+                                .text_size(14)
+                            )
+                            .push(Text::new("CPUs:").size(14))
+                            .push(
+                                text_input("e.g. 0,2-3", &self.affinity_input)
+                                    .on_input(Message::AffinityInput)
+                                    .padding(8)
+                                    .size(14)
+                                    .width(Length::Fixed(100.0))
+                            )
+                            .push({
+                                let apply = button(Text::new("Apply").size(14))
+                                    .style(theme::Button::Custom(Box::new(CustomButtonStyle {
+                                        background: palette.accent,
+                                        hover_background: palette.accent_hover,
+                                        text_color: Color::WHITE,
+                                        border_radius: 6.0,
+                                        border_width: 0.0,
+                                        border_color: Color::TRANSPARENT,
+                                    })))
+                                    .padding(8);
+                                match affinity {
+                                    Some(cpus) => apply.on_press(Message::SetAffinity(selected_pid, cpus)),
+                                    None => apply,
+                                }
+                            })
+                            .push(
+                                button(Text::new("All CPUs").size(14))
+                                    .on_press(Message::SetAffinity(selected_pid, (0..num_cpus::get()).collect()))
+                                    .style(theme::Button::Custom(Box::new(CustomButtonStyle {
+                                        background: palette.accent,
+                                        hover_background: palette.accent_hover,
+                                        text_color: Color::WHITE,
+                                        border_radius: 6.0,
+                                        border_width: 0.0,
+                                        border_color: Color::TRANSPARENT,
+                                    })))
+                                    .padding(8)
+                            )
+                            .push(
+                                checkbox("Remember for this app", self.remember_process_settings, Message::RememberProcessSettings)
+                                    .size(16)
+                            )
+                    )
+                    .push({
+                        let mut row = Row::new()
+                            .spacing(10)
+                            .push(Text::new("Limit:").size(14))
+                            .push(
+                                text_input("Memory, e.g. 1G", &self.memory_limit_input)
+                                    .on_input(Message::MemoryLimitInput)
+                                    .padding(8)
+                                    .size(14)
+                                    .width(Length::Fixed(130.0))
+                            )
                             .push(
-                                button(Text::new("30m").size(14))
-                                    .on_press(Message::SetDeadline(
-                                        selected_pid,
-                                        TimeInterval::ThirtyMinutes,
-                                    ))
+                                text_input("CPU %", &self.cpu_limit_input)
+                                    .on_input(Message::CpuLimitInput)
+                                    .padding(8)
+                                    .size(14)
+                                    .width(Length::Fixed(80.0))
+                            )
+                            .push({
+                                let apply = button(Text::new("Apply Limits").size(14))
                                     .style(theme::Button::Custom(Box::new(CustomButtonStyle {
-                                        background: ACCENT_BLUE,
-                                        hover_background: ACCENT_BLUE_HOVER,
+                                        background: palette.accent,
+                                        hover_background: palette.accent_hover,
                                         text_color: Color::WHITE,
                                         border_radius: 6.0,
                                         border_width: 0.0,
                                         border_color: Color::TRANSPARENT,
                                     })))
+                                    .padding(8);
+                                match limits {
+                                    Some(limits) if !limits.is_empty() => apply.on_press(Message::SetLimits(selected_pid, limits)),
+                                    _ => apply,
+                                }
+                            });
+                        if !current_limits.is_empty() {
+                            row = row
+                                .push(
+                                    button(Text::new("Remove Limits").size(14))
+                                        .on_press(Message::SetLimits(selected_pid, ResourceLimits::default()))
+                                        .style(theme::Button::Custom(Box::new(CustomButtonStyle {
+                                            background: palette.warning,
+                                            hover_background: palette.warning_hover,
+                                            text_color: Color::WHITE,
+                                            border_radius: 6.0,
+                                            border_width: 0.0,
+                                            border_color: Color::TRANSPARENT,
+                                        })))
+                                        .padding(8)
+                                )
+                                .push(text(format!("Limited to {}", current_limits)).size(14).style(palette.secondary_text));
+                        }
+                        row
+                    })
+                    .push({
+                        let budget_minutes = task::parse_minutes(&self.budget_input);
+                        let mut row = Row::new()
+                            .spacing(10)
+                            .push(Text::new("Daily Limit:").size(14))
+                            .push(
+                                text_input("Minutes per day", &self.budget_input)
+                                    .on_input(Message::BudgetInput)
                                     .padding(8)
+                                    .size(14)
                             )
                             .push(
-                                button(Text::new("1h").size(14))
-                                    .on_press(Message::SetDeadline(
-                                        selected_pid,
-                                        TimeInterval::OneHour,
-                                    ))
+                                button(Text::new("Set Limit").size(14))
+                                    .on_press_maybe(
+                                        budget_minutes.map(|minutes| Message::SetDailyBudget(selected_name.clone(), minutes))
+                                    )
                                     .style(theme::Button::Custom(Box::new(CustomButtonStyle {
-                                        background: ACCENT_BLUE,
-                                        hover_background: ACCENT_BLUE_HOVER,
+                                        background: palette.accent,
+                                        hover_background: palette.accent_hover,
                                         text_color: Color::WHITE,
                                         border_radius: 6.0,
                                         border_width: 0.0,
@@ -415,14 +1281,37 @@ impl TaskManager {
                                     .padding(8)
                             )
                             .push(
-                                button(Text::new("2h").size(14))
-                                    .on_press(Message::SetDeadline(
-                                        selected_pid,
-                                        TimeInterval::TwoHours,
-                                    ))
+                                button(Text::new("Remove Limit").size(14))
+                                    .on_press(Message::ClearDailyBudget(selected_name.clone()))
+                                    .style(theme::Button::Custom(Box::new(CustomButtonStyle {
+                                        background: palette.warning,
+                                        hover_background: palette.warning_hover,
+                                        text_color: Color::WHITE,
+                                        border_radius: 6.0,
+                                        border_width: 0.0,
+                                        border_color: Color::TRANSPARENT,
+                                    })))
+                                    .padding(8)
+                            );
+                        // A typo must not turn into some default limit that ends the app
+                        if budget_minutes.is_none() && !self.budget_input.trim().is_empty() {
+                            row = row.push(text("Enter whole minutes per day").size(14).style(palette.warning_hover));
+                        }
+                        row
+                    })
+                    .push(
+                        Row::new()
+                            .spacing(10)
+                            .push(
+                                button(Text::new(if is_suspended { "Resume" } else { "Suspend" }).size(14))
+                                    .on_press(if is_suspended {
+                                        Message::ResumeTask(selected_pid)
+                                    } else {
+                                        Message::SuspendTask(selected_pid)
+                                    })
                                     .style(theme::Button::Custom(Box::new(CustomButtonStyle {
-                                        background: ACCENT_BLUE,
-                                        hover_background: ACCENT_BLUE_HOVER,
+                                        background: palette.accent,
+                                        hover_background: palette.accent_hover,
                                         text_color: Color::WHITE,
                                         border_radius: 6.0,
                                         border_width: 0.0,
@@ -431,40 +1320,24 @@ impl TaskManager {
                                     .padding(8)
                             )
                             .push(
-                                Row::new()
-                                    .spacing(10)
-                                    .push(
-                                        text_input("Minutes", &self.custom_deadline)
-                                            .on_input(Message::CustomDeadlineInput)
-                                            .padding(8)
-                                            .size(14)
-                                    )
-                                    .push(
-                                        button(Text::new("Set Custom").size(14))
-                                            .on_press({
-                                                let minutes = self.custom_deadline.parse::<i64>().unwrap_or(30);
-                                                Message::SetDeadline(
-                                                    selected_pid,
-                                                    TimeInterval::Custom(Local::now() + chrono::Duration::minutes(minutes)),
-                                                )
-                                            })
-                                            .style(theme::Button::Custom(Box::new(CustomButtonStyle {
-                                                background: ACCENT_BLUE,
-                                                hover_background: ACCENT_BLUE_HOVER,
-                                                text_color: Color::WHITE,
-                                                border_radius: 6.0,
-                                                border_width: 0.0,
-                                                border_color: Color::TRANSPARENT,
-                                            })))
-                                            .padding(8)
-                                    )
+                                button(Text::new("End Task").size(14))
+                                    .on_press(Message::TerminateTask(selected_pid))
+                                    .style(theme::Button::Custom(Box::new(CustomButtonStyle {
+                                        background: palette.warning,
+                                        hover_background: palette.warning_hover,
+                                        text_color: Color::WHITE,
+                                        border_radius: 6.0,
+                                        border_width: 0.0,
+                                        border_color: Color::TRANSPARENT,
+                                    })))
+                                    .padding(8)
                             )
                             .push(
-                                button(Text::new("Clear").size(14))
-                                    .on_press(Message::ClearDeadline(selected_pid))
+                                button(Text::new(if user_protected { "Unprotect App" } else { "Protect App" }).size(14))
+                                    .on_press(Message::ToggleProtectedApp(selected_name))
                                     .style(theme::Button::Custom(Box::new(CustomButtonStyle {
-                                        background: WARNING_COLOR,
-                                        hover_background: WARNING_COLOR_HOVER,
+                                        background: palette.accent,
+                                        hover_background: palette.accent_hover,
                                         text_color: Color::WHITE,
                                         border_radius: 6.0,
                                         border_width: 0.0,
@@ -473,27 +1346,14 @@ impl TaskManager {
                                     .padding(8)
                             )
                     )
-                    .push(
-                        button(Text::new("End Task").size(14))
-                            .on_press(Message::TerminateTask(selected_pid))
-                            .style(theme::Button::Custom(Box::new(CustomButtonStyle {
-                                background: WARNING_COLOR,
-                                hover_background: WARNING_COLOR_HOVER,
-                                text_color: Color::WHITE,
-                                border_radius: 6.0,
-                                border_width: 0.0,
-                                border_color: Color::TRANSPARENT,
-                            })))
-                            .padding(8)
-                    )
             )
             .padding(15)
             .style(theme::Container::Custom(Box::new(CustomStyle {
-                background: DARK_BG_LIGHTER,
-                text: DARK_TEXT,
+                background: palette.surface,
+                text: palette.text,
                 border_radius: 8.0,
                 border_width: 1.0,
-                border_color: BORDER_COLOR,
+                border_color: palette.border,
             })))
         } else {
             Container::new(
@@ -502,11 +1362,11 @@ impl TaskManager {
             )
             .padding(15)
             .style(theme::Container::Custom(Box::new(CustomStyle {
-                background: DARK_BG_LIGHTER,
-                text: DARK_TEXT,
+                background: palette.surface,
+                text: palette.text,
                 border_radius: 8.0,
                 border_width: 1.0,
-                border_color: BORDER_COLOR,
+                border_color: palette.border,
             })))
         };
 
@@ -515,18 +1375,21 @@ impl TaskManager {
             .push(text("Name").width(Length::FillPortion(4)).size(14))
             .push(text("CPU").width(Length::Fixed(100.0)).size(14))
             .push(text("Memory").width(Length::Fixed(100.0)).size(14))
+            .push(text("Status").width(Length::Fixed(100.0)).size(14))
             .push(text("Deadline").width(Length::Fixed(150.0)).size(14));
 
         let process_list = {
             let mut rows = Vec::new();
-            for (pid, task) in sorted_tasks {
-                let status = task.get_status();
+            for (_, task) in sorted_tasks {
+                let status = task.status.clone();
                 let row_color = match status {
-                    ProcessStatus::DeadlineReached => WARNING_COLOR,
-                    _ => DARK_BG,
+                    ProcessStatus::DeadlineReached => palette.warning,
+                    ProcessStatus::Suspended => palette.accent_muted,
+                    ProcessStatus::Failed => Color { a: 0.4, ..palette.warning },
+                    _ => palette.background,
                 };
 
-                let is_selected = self.selected_task == Some(*pid);
+                let is_selected = self.selected_task == Some(task.pid);
 
                 // Create row with icon
                 let task_row = button(
@@ -547,30 +1410,31 @@ impl TaskManager {
                                 }
                             )
                             .push(text(&task.name).width(Length::FillPortion(4)))
-                            .push(text(&format!("{:.1}%", task.cpu_usage)).width(Length::Fixed(100.0)))
-                            .push(text(&format!("{:.1} MB", task.memory_usage as f64 / 1024.0 / 1024.0)).width(Length::Fixed(100.0)))
-                            .push(text(&task.format_deadline()).width(Length::Fixed(150.0)))
+                            .push(text(format!("{:.1}%", task.cpu_usage)).width(Length::Fixed(100.0)))
+                            .push(text(format!("{:.1} MB", task.memory_usage as f64 / 1024.0 / 1024.0)).width(Length::Fixed(100.0)))
+                            .push(text(status.label()).width(Length::Fixed(100.0)).style(palette.secondary_text))
+                            .push(text(task.format_deadline()).width(Length::Fixed(150.0)))
                     )
                     .width(Length::Fill)
                     .padding(10)
                 )
-                .on_press(Message::TaskSelected(*pid))
+                .on_press(Message::TaskSelected(task.pid))
                 .style(theme::Button::Custom(Box::new(CustomButtonStyle {
                     background: if is_selected {
-                        ROW_HOVER
+                        palette.row_hover
                     } else {
                         row_color
                     },
-                    hover_background: ROW_HOVER,
+                    hover_background: palette.row_hover,
                     text_color: if status == ProcessStatus::DeadlineReached {
                         Color::WHITE
                     } else {
-                        DARK_TEXT
+                        palette.text
                     },
                     border_radius: 6.0,
                     border_width: 1.0,
-                    border_color: BORDER_COLOR,
-                })));;
+                    border_color: palette.border,
+                })));
 
                 rows.push(task_row.into());
             }
@@ -581,6 +1445,8 @@ impl TaskManager {
             .spacing(20)
             .push(header)
             .push(search_bar)
+            .push(focus_panel)
+            .push(launch_panel)
             .push(
                 Container::new(
                     Column::new()
@@ -589,11 +1455,11 @@ impl TaskManager {
                             Container::new(table_header)
                                 .padding(10)
                                 .style(theme::Container::Custom(Box::new(CustomStyle {
-                                    background: Color { a: 0.1, ..DARK_BG },
-                                    text: DARK_TEXT,
+                                    background: Color { a: 0.1, ..palette.background },
+                                    text: palette.text,
                                     border_radius: 6.0,
                                     border_width: 1.0,
-                                    border_color: BORDER_COLOR,
+                                    border_color: palette.border,
                                 })))
                         )
                         .push(
@@ -605,168 +1471,24 @@ impl TaskManager {
                 .height(Length::Fill)
             )
             .push(selected_controls);
+        let content = match &self.toast {
+            Some(toast) => content.push(self.view_toast(toast)),
+            None => content,
+        };
 
         Container::new(content)
             .width(Length::Fill)
             .height(Length::Fill)
             .padding(20)
             .style(theme::Container::Custom(Box::new(CustomStyle {
-                background: DARK_BG,
-                text: DARK_TEXT,
+                background: palette.background,
+                text: palette.text,
                 border_radius: 12.0,
                 border_width: 1.0,
-                border_color: BORDER_COLOR,
+                border_color: palette.border,
             })))
             .into()
     }
-
-    fn try_save_application_work(&self, process_name: &str) -> bool {
-        let window_class = match process_name.to_uppercase().as_str() {
-            "WINWORD.EXE" => "OpusApp",
-            "EXCEL.EXE" => "XLMAIN",
-            "POWERPNT.EXE" => "PPTFrameClass",
-            "NOTEPAD.EXE" => "Notepad",
-            "NOTEPAD++.EXE" => "Notepad++",
-            _ => "",
-        };
-
-        unsafe {
-            let mut window = if !window_class.is_empty() {
-                let wide_class: Vec<u16> = OsString::from(window_class)
-                    .encode_wide()
-                    .chain(std::iter::once(0))
-                    .collect();
-                find_window_by_class_and_name(Some(window_class), None)
-            } else {
-                find_window_by_class_and_name(None, Some(process_name))
-            };
-
-            if window.is_none() {
-                window = find_window_by_class_and_name(None, Some(process_name));
-            }
-
-            if window.is_none() {
-                use windows::Win32::UI::WindowsAndMessaging::{EnumWindows, GetWindowTextW};
-                use windows::Win32::Foundation::{BOOL, LPARAM};
-                use std::sync::Mutex;
-                
-                let found_window = Arc::new(Mutex::new(None));
-                let target_name = process_name.to_lowercase();
-                
-                unsafe extern "system" fn enum_callback(hwnd: HWND, lparam: LPARAM) -> BOOL {
-                    let found_window = (lparam.0 as *mut Mutex<Option<HWND>>)
-                        .as_ref()
-                        .unwrap();
-                    
-                    let mut title: [u16; 512] = [0; 512];
-                    let len = GetWindowTextW(hwnd, &mut title);
-                    let window_title = String::from_utf16_lossy(&title[..len as usize])
-                        .to_lowercase();
-                    
-                    if window_title.contains(&(lparam.0 as *const String)
-                        .as_ref()
-                        .unwrap()
-                        .to_lowercase()
-                    ) {
-                        *found_window.lock().unwrap() = Some(hwnd);
-                        BOOL(0)
-                    } else {
-                        BOOL(1)
-                    }
-                }
-                
-                EnumWindows(
-                    Some(enum_callback),
-                    LPARAM(&target_name as *const String as isize)
-                );
-                
-                window = *found_window.lock().unwrap();
-            }
-
-            if window.is_none() {
-                println!("❌ Could not find window for process: {}", process_name);
-                return false;
-            }
-
-            let mut enigo = Enigo::new();
-            enigo.key_down(Key::Control);
-            enigo.key_click(Key::Layout('s'));
-            enigo.key_up(Key::Control);
-
-            true
-        }
-    }
-
-    fn should_try_save(&self, process_name: &str) -> bool {
-        let process_upper = process_name.to_uppercase();
-        
-        // Add more application categories that might need special save handling
-        OFFICE_APPS.iter().any(|&app| process_upper.contains(&app.to_uppercase())) ||
-        TEXT_EDITORS.iter().any(|&app| process_upper.contains(&app.to_uppercase())) ||
-        IDES.iter().any(|&app| process_upper.contains(&app.to_uppercase())) ||
-        DESIGN_APPS.iter().any(|&app| process_upper.contains(&app.to_uppercase())) ||
-        DEVELOPMENT_TOOLS.iter().any(|&app| process_upper.contains(&app.to_uppercase())) ||
-        CREATIVE_TOOLS.iter().any(|&app| process_upper.contains(&app.to_uppercase())) ||
-        BROWSER_APPS.iter().any(|&app| process_upper.contains(&app.to_uppercase())) ||
-        PRODUCTIVITY_APPS.iter().any(|&app| process_upper.contains(&app.to_uppercase())) ||
-        DEVELOPMENT_APPS.iter().any(|&app| process_upper.contains(&app.to_uppercase()))
-    }
-
-    fn terminate_process(&mut self, pid: u32) {
-        if let Some(process) = self.system.process(Pid::from_u32(pid)) {
-            let name = process.name();
-            println!("📝 Process name: {}", name);
-
-            // Try to save work if it's a supported application
-            if self.should_try_save(name) {
-                println!("💾 Attempting to save work before termination...");
-                if self.try_save_application_work(name) {
-                    println!("✅ Save attempt completed");
-                    // Give the application more time to finish saving
-                    thread::sleep(Duration::from_secs(2));
-                } else {
-                    println!("⚠️ Could not attempt save");
-                }
-            }
-
-            unsafe {
-                let process_handle = OpenProcess(
-                    PROCESS_ACCESS_RIGHTS(PROCESS_TERMINATE.0),
-                    false,
-                    pid
-                );
-
-                match process_handle {
-                    Ok(handle) => {
-                        if handle.is_invalid() {
-                            println!("❌ Failed to get process handle - Access Denied");
-                            return;
-                        }
-
-                        // Try to save one more time before terminating
-                        if self.should_try_save(name) {
-                            self.try_save_application_work(name);
-                            thread::sleep(Duration::from_secs(1));
-                        }
-
-                        let result = TerminateProcess(handle, 1);
-                        if result.as_bool() {
-                            println!("✅ Process terminated successfully");
-                            self.tasks.remove(&pid);
-                            self.selected_task = None;
-                        } else {
-                            println!("❌ Failed to terminate process - Operation Failed");
-                        }
-
-                        let _ = CloseHandle(HANDLE(handle.0));
-                    }
-                    Err(_) => {
-                        println!("❌ Failed to open process - Access Denied");
-                    }
-                }
-            }
-        }
-    }
 }
 
 impl Application for TaskManager {
@@ -776,13 +1498,41 @@ impl Application for TaskManager {
     type Flags = ();
 
     fn new(_flags: ()) -> (Self, Command<Message>) {
+        let backend = Backend::connect();
+        if backend.is_remote() {
+            info!("Attached to running TaskTide daemon");
+        }
+        let config = Config::load();
         (
             TaskManager {
-                system: System::new_all(),
+                backend,
                 tasks: HashMap::new(),
+                icons: HashMap::new(),
+                palette: Palette::from_config(&config.colors),
+                config,
+                focus: None,
                 selected_task: None,
                 search_query: String::new(),
                 custom_deadline: String::new(),
+                budget_input: String::new(),
+                focus_minutes: "25".to_string(),
+                focus_block_browsers: true,
+                focus_block_games: true,
+                focus_require_confirmation: true,
+                focus_suspend_instead: false,
+                focus_confirm_input: String::new(),
+                launch_command: String::new(),
+                launch_limit: String::new(),
+                launched: Vec::new(),
+                recently_ended: Vec::new(),
+                affinity_input: String::new(),
+                memory_limit_input: String::new(),
+                cpu_limit_input: String::new(),
+                remember_process_settings: false,
+                pending_termination: None,
+                history: None,
+                settings: None,
+                toast: None,
             },
             Command::none(),
         )
@@ -793,7 +1543,7 @@ impl Application for TaskManager {
     }
 
     fn subscription(&self) -> Subscription<Message> {
-        every(Duration::from_secs(1)).map(|_| Message::Tick)
+        every(self.config.refresh_interval()).map(|_| Message::Tick)
     }
 
     fn update(&mut self, message: Message) -> Command<Message> {
@@ -804,86 +1554,310 @@ impl Application for TaskManager {
             }
             Message::SearchInput(query) => {
                 self.search_query = query;
-                self.update_tasks();
                 Command::none()
             }
             Message::CustomDeadlineInput(input) => {
                 self.custom_deadline = input;
                 Command::none()
             }
+            Message::BudgetInput(input) => {
+                self.budget_input = input;
+                Command::none()
+            }
+            Message::SetDailyBudget(name, minutes) => {
+                self.send(Request::SetDailyBudget(name, minutes));
+                Command::none()
+            }
+            Message::ClearDailyBudget(name) => {
+                self.send(Request::ClearDailyBudget(name));
+                Command::none()
+            }
+            Message::FocusMinutesInput(input) => {
+                self.focus_minutes = input;
+                Command::none()
+            }
+            Message::FocusBlockBrowsers(enabled) => {
+                self.focus_block_browsers = enabled;
+                Command::none()
+            }
+            Message::FocusBlockGames(enabled) => {
+                self.focus_block_games = enabled;
+                Command::none()
+            }
+            Message::FocusRequireConfirmation(enabled) => {
+                self.focus_require_confirmation = enabled;
+                Command::none()
+            }
+            Message::FocusSuspendInstead(enabled) => {
+                self.focus_suspend_instead = enabled;
+                Command::none()
+            }
+            Message::StartFocus(minutes) => {
+                let minutes = minutes as i64;
+                let mut blocklist = self.config.focus_blocklist.clone();
+                if self.focus_block_browsers {
+                    blocklist.extend(self.config.apps("browsers").iter().cloned());
+                }
+                if self.focus_block_games {
+                    blocklist.extend(self.config.apps("games").iter().cloned());
+                }
+                let action = if self.focus_suspend_instead {
+                    DeadlineAction::Suspend
+                } else {
+                    DeadlineAction::Terminate
+                };
+                self.focus_confirm_input.clear();
+                self.send(Request::StartFocus {
+                    minutes,
+                    blocklist,
+                    require_confirmation: self.focus_require_confirmation,
+                    action,
+                });
+                Command::none()
+            }
+            Message::FocusConfirmInput(input) => {
+                self.focus_confirm_input = input;
+                Command::none()
+            }
+            Message::ShowHistory => {
+                self.history = Some(audit::recent(HISTORY_LIMIT));
+                Command::none()
+            }
+            Message::CloseHistory => {
+                self.history = None;
+                Command::none()
+            }
+            Message::ShowSettings => {
+                self.settings = Some(SettingsForm::new(&self.config));
+                Command::none()
+            }
+            Message::CloseSettings => {
+                self.settings = None;
+                Command::none()
+            }
+            Message::SettingsInput(field, value) => {
+                if let Some(form) = self.settings.as_mut() {
+                    form.set(field, value);
+                }
+                Command::none()
+            }
+            Message::SettingsSmartSaveToggled(category, enabled) => {
+                if let Some(form) = self.settings.as_mut() {
+                    form.toggle_smart_save(category, enabled);
+                }
+                Command::none()
+            }
+            Message::SettingsBlockUnverifiedToggled(enabled) => {
+                if let Some(form) = self.settings.as_mut() {
+                    form.block_unverified = enabled;
+                }
+                Command::none()
+            }
+            Message::SaveSettings => {
+                let Some(form) = &self.settings else {
+                    return Command::none();
+                };
+                let mut config = self.config.clone();
+                match form.apply(&mut config) {
+                    Ok(()) => {
+                        if self.send(Request::SaveConfig(Box::new(config))) {
+                            self.settings = None;
+                            self.toast = Some(Toast::info("Settings saved".to_string()));
+                        }
+                    }
+                    Err(e) => self.toast = Some(Toast::error(e)),
+                }
+                Command::none()
+            }
+            Message::EndFocus => {
+                let confirmation = self.focus_confirm_input.clone();
+                self.send(Request::EndFocus { confirmation });
+                Command::none()
+            }
             Message::TerminateTask(pid) => {
-                self.terminate_process(pid);
+                let key = self.tasks.get(&pid).map(|task| budget_key(&task.name)).unwrap_or_default();
+                let skip_confirmation = self.config.skip_end_task_confirmation.contains(&key);
+                let protected = self.tasks
+                    .get(&pid)
+                    .is_some_and(|task| task.effective_protection().is_some());
+                if protected {
+                    // Refused straight away, no need to ask
+                    self.terminate_process(pid, None);
+                } else if skip_confirmation {
+                    let end_children = !self.config.end_task_keep_children.contains(&key);
+                    let children = task::descendants(self.tasks.values(), pid);
+                    return self.end_task(pid, if end_children { &children } else { &[] });
+                } else {
+                    self.pending_termination = self.preview_termination(pid);
+                }
                 Command::none()
             }
-            Message::SetDeadline(pid, interval) => {
-                if let Some(task) = self.tasks.get_mut(&pid) {
-                    match interval {
-                        TimeInterval::ThirtyMinutes => task.deadline = Some(Local::now() + chrono::Duration::minutes(30)),
-                        TimeInterval::OneHour => task.deadline = Some(Local::now() + chrono::Duration::hours(1)),
-                        TimeInterval::TwoHours => task.deadline = Some(Local::now() + chrono::Duration::hours(2)),
-                        TimeInterval::Custom(deadline) => task.deadline = Some(deadline),
+            Message::HooksFinished(verdicts) => {
+                for (pid, vetoed_by) in verdicts {
+                    self.terminate_process(pid, vetoed_by);
+                }
+                Command::none()
+            }
+            Message::ConfirmTermination => {
+                if let Some(preview) = self.pending_termination.take() {
+                    if preview.dont_ask_again {
+                        self.send(Request::SkipEndTaskConfirmation {
+                            name: preview.name.clone(),
+                            end_children: preview.end_children,
+                        });
                     }
+                    return self.end_task(preview.pid, if preview.end_children { &preview.children } else { &[] });
+                }
+                Command::none()
+            }
+            Message::CancelTermination => {
+                self.pending_termination = None;
+                Command::none()
+            }
+            Message::EndChildrenToggled(enabled) => {
+                if let Some(preview) = &mut self.pending_termination {
+                    preview.end_children = enabled;
+                }
+                Command::none()
+            }
+            Message::DontAskAgainToggled(enabled) => {
+                if let Some(preview) = &mut self.pending_termination {
+                    preview.dont_ask_again = enabled;
                 }
                 Command::none()
             }
+            Message::SetDeadline(pid, interval) => {
+                self.send(Request::SetDeadline(pid, interval));
+                Command::none()
+            }
             Message::ClearDeadline(pid) => {
-                if let Some(task) = self.tasks.get_mut(&pid) {
-                    task.deadline = None;
+                self.send(Request::ClearDeadline(pid));
+                Command::none()
+            }
+            Message::SetDeadlineAction(pid, action) => {
+                self.send(Request::SetDeadlineAction(pid, action));
+                Command::none()
+            }
+            Message::SuspendTask(pid) => {
+                self.send(Request::Suspend(pid));
+                Command::none()
+            }
+            Message::ResumeTask(pid) => {
+                self.send(Request::Resume(pid));
+                Command::none()
+            }
+            Message::SetPriority(pid, priority) => {
+                let remember = self.remember_process_settings;
+                self.send(Request::SetPriority { pid, priority, remember });
+                Command::none()
+            }
+            Message::AffinityInput(input) => {
+                self.affinity_input = input;
+                Command::none()
+            }
+            Message::MemoryLimitInput(input) => {
+                self.memory_limit_input = input;
+                Command::none()
+            }
+            Message::CpuLimitInput(input) => {
+                self.cpu_limit_input = input;
+                Command::none()
+            }
+            Message::SetLimits(pid, limits) => {
+                if self.send(Request::SetLimits { pid, limits }) {
+                    self.toast = Some(Toast::info(if limits.is_empty() {
+                        format!("Lifted the limits of PID {}", pid)
+                    } else {
+                        format!("PID {} limited to {}", pid, limits)
+                    }));
                 }
                 Command::none()
             }
+            Message::SetAffinity(pid, cpus) => {
+                let remember = self.remember_process_settings;
+                self.send(Request::SetAffinity { pid, cpus, remember });
+                Command::none()
+            }
+            Message::RememberProcessSettings(enabled) => {
+                self.remember_process_settings = enabled;
+                Command::none()
+            }
+            Message::OverrideProtection(pid, enabled) => {
+                self.send(Request::OverrideProtection(pid, enabled));
+                Command::none()
+            }
+            Message::ToggleProtectedApp(name) => {
+                self.send(Request::ToggleProtectedApp(name));
+                Command::none()
+            }
             Message::Tick => {
-                self.update_tasks();
+                self.refresh();
+                if self.toast.as_ref().is_some_and(|toast| toast.shown_at.elapsed() >= TOAST_DURATION) {
+                    self.toast = None;
+                }
+                Command::none()
+            }
+            Message::Relaunch(pid) => {
+                self.relaunch(pid);
+                Command::none()
+            }
+            Message::LaunchCommandInput(input) => {
+                self.launch_command = input;
+                Command::none()
+            }
+            Message::LaunchLimitInput(input) => {
+                self.launch_limit = input;
+                Command::none()
+            }
+            Message::Launch => {
+                self.launch();
+                Command::none()
+            }
+            Message::DismissToast => {
+                self.toast = None;
                 Command::none()
             }
             Message::CheckDeadlines => {
-                self.update_tasks();
+                self.refresh();
                 Command::none()
             }
         }
     }
 
-    fn view(&self) -> Element<Message> {
+    fn view(&self) -> Element<'_, Message> {
         self.view()
     }
 }
 
-fn find_window_by_class_and_name(class_name: Option<&str>, window_name: Option<&str>) -> Option<HWND> {
-    unsafe {
-        let wide_class: Option<Vec<u16>> = class_name.map(|s| {
-            OsString::from(s)
-                .encode_wide()
-                .chain(std::iter::once(0))
-                .collect()
-        });
-        
-        let wide_name: Option<Vec<u16>> = window_name.map(|s| {
-            OsString::from(s)
-                .encode_wide()
-                .chain(std::iter::once(0))
-                .collect()
-        });
-
-        let class_ptr = wide_class
-            .as_ref()
-            .map(|v| PCWSTR::from_raw(v.as_ptr()))
-            .unwrap_or(PCWSTR::null());
-
-        let name_ptr = wide_name
-            .as_ref()
-            .map(|v| PCWSTR::from_raw(v.as_ptr()))
-            .unwrap_or(PCWSTR::null());
-
-        let hwnd = FindWindowW(class_ptr, name_ptr);
-        if hwnd.0 == 0 {
-            None
-        } else {
-            Some(hwnd)
+fn main() -> iced::Result {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let log_level = match logging::take_level_arg(&mut args) {
+        Ok(level) => level,
+        Err(e) => {
+            eprintln!("tasktide: {}", e);
+            std::process::exit(2);
         }
+    };
+    // Only in place of a subcommand, so `run -- app --daemon` passes it on to the app
+    let daemon = args.first().is_some_and(|arg| arg == "--daemon");
+    // Command-line invocations only log problems unless asked, their output is the result
+    let command_line = !args.is_empty() && !daemon;
+    logging::init(log_level.unwrap_or(if command_line { LevelFilter::Warn } else { LevelFilter::Info }));
+
+    // Headless mode: enforce deadlines in the background, the GUI attaches when opened
+    if daemon {
+        if let Err(e) = daemon::run() {
+            error!("TaskTide daemon stopped: {}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    // Any other arguments are a command-line invocation
+    if !args.is_empty() {
+        std::process::exit(cli::run(&args));
     }
-}
 
-fn main() -> iced::Result {
     let icon = icon::from_file_data(
         include_bytes!("../assets/logo.png"),
         Some(image::ImageFormat::Png),
@@ -962,15 +1936,23 @@ impl button::StyleSheet for CustomButtonStyle {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(not(windows), allow(dead_code))]
 struct ProcessIcon {
     handle: Handle,
 }
 
 impl ProcessIcon {
+    #[cfg(windows)]
     fn new(handle: Handle) -> Self {
         ProcessIcon { handle }
     }
 
+    #[cfg(not(windows))]
+    pub fn from_exe_path(_path: &str) -> Option<Self> {
+        None
+    }
+
+    #[cfg(windows)]
     pub fn from_exe_path(path: &str) -> Option<Self> {
         unsafe {
             let mut bi = BITMAPINFO {
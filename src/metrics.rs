@@ -0,0 +1,73 @@
+// Prometheus text exposition of what the daemon samples and does
+use crate::quota::budget_key;
+use crate::scheduler::{Snapshot, Stats};
+use crate::task::{DeadlineAction, Task};
+use std::cmp::Ordering;
+use std::fmt::Write;
+
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+pub fn render(snapshot: &Snapshot, stats: &Stats) -> String {
+    let mut out = String::new();
+    let config = &snapshot.config.metrics;
+
+    let mut tasks: Vec<&Task> = if config.processes.is_empty() {
+        let mut busiest: Vec<&Task> = snapshot.tasks.iter().collect();
+        busiest.sort_by(|a, b| b.cpu_usage.partial_cmp(&a.cpu_usage).unwrap_or(Ordering::Equal));
+        busiest.truncate(config.top_processes);
+        busiest
+    } else {
+        let wanted: Vec<String> = config.processes.iter().map(|name| budget_key(name)).collect();
+        snapshot.tasks.iter().filter(|task| wanted.contains(&budget_key(&task.name))).collect()
+    };
+    tasks.sort_by_key(|task| task.pid);
+
+    header(&mut out, "tasktide_process_cpu_percent", "gauge", "CPU usage of a process, 100 per fully used core");
+    for task in &tasks {
+        let _ = writeln!(out, "tasktide_process_cpu_percent{} {}", process_labels(task), task.cpu_usage);
+    }
+    header(&mut out, "tasktide_process_memory_bytes", "gauge", "Resident memory of a process");
+    for task in &tasks {
+        let _ = writeln!(out, "tasktide_process_memory_bytes{} {}", process_labels(task), task.memory_usage);
+    }
+
+    header(&mut out, "tasktide_processes", "gauge", "Processes currently running");
+    let _ = writeln!(out, "tasktide_processes {}", snapshot.tasks.len());
+
+    header(&mut out, "tasktide_deadlines_active", "gauge", "Processes with a pending deadline, by what happens when it is reached");
+    for action in DeadlineAction::ALL {
+        let count = snapshot.tasks
+            .iter()
+            .filter(|task| task.deadline.is_some() && task.deadline_action == action)
+            .count();
+        let _ = writeln!(out, "tasktide_deadlines_active{{action=\"{:?}\"}} {}", action, count);
+    }
+
+    counter(&mut out, "tasktide_terminations_total", "Processes ended by TaskTide", stats.terminations);
+    counter(&mut out, "tasktide_termination_failures_total", "Attempts to end a process that failed", stats.termination_failures);
+    counter(&mut out, "tasktide_smart_save_attempts_total", "Save attempts made before ending a process", stats.smart_save_attempts);
+    counter(&mut out, "tasktide_smart_save_failures_total", "Save attempts that could not be made", stats.smart_save_failures);
+
+    header(&mut out, "tasktide_sample_duration_seconds", "gauge", "How long the last process list refresh took");
+    let _ = writeln!(out, "tasktide_sample_duration_seconds {}", stats.sample_duration.as_secs_f64());
+    out
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn counter(out: &mut String, name: &str, help: &str, value: u64) {
+    header(out, name, "counter", help);
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+fn process_labels(task: &Task) -> String {
+    format!("{{pid=\"{}\",name=\"{}\"}}", task.pid, escape(&task.name))
+}
+
+// Label values escape backslash, double quote and newline
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
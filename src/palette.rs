@@ -0,0 +1,78 @@
+use crate::config::ColorConfig;
+use iced::Color;
+use log::warn;
+
+// Every color the GUI draws with; hover and muted shades follow from the configured ones
+#[derive(Debug, Clone, Copy)]
+pub struct Palette {
+    pub background: Color,
+    pub surface: Color,
+    pub text: Color,
+    pub secondary_text: Color,
+    pub border: Color,
+    pub row_hover: Color,
+    pub success: Color,
+    pub warning: Color,
+    pub warning_hover: Color,
+    pub accent: Color,
+    pub accent_hover: Color,
+    pub accent_muted: Color,
+}
+
+impl Palette {
+    pub fn from_config(colors: &ColorConfig) -> Self {
+        let defaults = ColorConfig::default();
+        let color = |value: &str, default: &str| {
+            parse_hex(value).unwrap_or_else(|| {
+                warn!("Ignoring invalid color '{}', expected #rrggbb", value);
+                parse_hex(default).unwrap_or(Color::BLACK)
+            })
+        };
+        let background = color(&colors.background, &defaults.background);
+        let surface = color(&colors.surface, &defaults.surface);
+        let text = color(&colors.text, &defaults.text);
+        let accent = color(&colors.accent, &defaults.accent);
+        let warning = color(&colors.warning, &defaults.warning);
+
+        Self {
+            background,
+            surface,
+            text,
+            secondary_text: mix(text, background, 0.27),
+            border: lighten(surface, 0.1),
+            row_hover: lighten(surface, 0.05),
+            success: color(&colors.success, &defaults.success),
+            warning,
+            warning_hover: lighten(warning, 0.1),
+            accent,
+            accent_hover: lighten(accent, 0.1),
+            accent_muted: mix(accent, surface, 0.2),
+        }
+    }
+}
+
+pub fn parse_hex(value: &str) -> Option<Color> {
+    let hex = value.strip_prefix('#')?;
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |range: std::ops::Range<usize>| u8::from_str_radix(&hex[range], 16).ok();
+    Some(Color::from_rgb8(channel(0..2)?, channel(2..4)?, channel(4..6)?))
+}
+
+fn lighten(color: Color, amount: f32) -> Color {
+    Color::from_rgb(
+        (color.r + amount).min(1.0),
+        (color.g + amount).min(1.0),
+        (color.b + amount).min(1.0),
+    )
+}
+
+// `amount` of the way from `from` to `to`
+fn mix(from: Color, to: Color, amount: f32) -> Color {
+    Color::from_rgb(
+        from.r + (to.r - from.r) * amount,
+        from.g + (to.g - from.g) * amount,
+        from.b + (to.b - from.b) * amount,
+    )
+}
@@ -0,0 +1,354 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+#[cfg(unix)]
+use std::time::Duration;
+#[cfg(unix)]
+use log::warn;
+
+// How long a process gets to exit after SIGTERM before it is sent SIGKILL
+#[cfg(unix)]
+const TERMINATE_GRACE: Duration = Duration::from_secs(2);
+// How long the kernel gets to tear a process down after SIGKILL
+#[cfg(unix)]
+const KILL_GRACE: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PriorityClass {
+    Idle,
+    BelowNormal,
+    Normal,
+    AboveNormal,
+    High,
+}
+
+impl PriorityClass {
+    pub const ALL: [PriorityClass; 5] = [
+        PriorityClass::Idle,
+        PriorityClass::BelowNormal,
+        PriorityClass::Normal,
+        PriorityClass::AboveNormal,
+        PriorityClass::High,
+    ];
+}
+
+impl fmt::Display for PriorityClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            PriorityClass::Idle => "Idle",
+            PriorityClass::BelowNormal => "Below Normal",
+            PriorityClass::Normal => "Normal",
+            PriorityClass::AboveNormal => "Above Normal",
+            PriorityClass::High => "High",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use super::PriorityClass;
+    use std::io;
+    use windows::Win32::Foundation::{CloseHandle, HANDLE, NTSTATUS};
+    use windows::Win32::System::Threading::{
+        OpenProcess, SetPriorityClass, SetProcessAffinityMask, ABOVE_NORMAL_PRIORITY_CLASS,
+        BELOW_NORMAL_PRIORITY_CLASS, HIGH_PRIORITY_CLASS, IDLE_PRIORITY_CLASS,
+        NORMAL_PRIORITY_CLASS, PROCESS_ACCESS_RIGHTS, PROCESS_SET_INFORMATION,
+        PROCESS_SUSPEND_RESUME,
+    };
+
+    #[link(name = "ntdll")]
+    extern "system" {
+        fn NtSuspendProcess(process: HANDLE) -> NTSTATUS;
+        fn NtResumeProcess(process: HANDLE) -> NTSTATUS;
+    }
+
+    // Opens the process, runs `action` on the handle and closes it again
+    fn with_process<T>(
+        pid: u32,
+        access: PROCESS_ACCESS_RIGHTS,
+        action: impl FnOnce(HANDLE) -> io::Result<T>,
+    ) -> io::Result<T> {
+        unsafe {
+            // HRESULT_FROM_WIN32 keeps the Win32 error code in the low word
+            let handle = OpenProcess(access, false, pid)
+                .map_err(|e| io::Error::from_raw_os_error(e.code().0 & 0xFFFF))?;
+            if handle.is_invalid() {
+                return Err(io::Error::from(io::ErrorKind::PermissionDenied));
+            }
+
+            let result = action(handle);
+            let _ = CloseHandle(handle);
+            result
+        }
+    }
+
+    fn nt_result(status: NTSTATUS) -> io::Result<()> {
+        if status.is_ok() {
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("NTSTATUS {:#010x}", status.0),
+            ))
+        }
+    }
+
+    pub fn suspend(pid: u32) -> io::Result<()> {
+        with_process(pid, PROCESS_SUSPEND_RESUME, |handle| unsafe {
+            nt_result(NtSuspendProcess(handle))
+        })
+    }
+
+    pub fn resume(pid: u32) -> io::Result<()> {
+        with_process(pid, PROCESS_SUSPEND_RESUME, |handle| unsafe {
+            nt_result(NtResumeProcess(handle))
+        })
+    }
+
+    pub fn set_priority(pid: u32, priority: PriorityClass) -> io::Result<()> {
+        let class = match priority {
+            PriorityClass::Idle => IDLE_PRIORITY_CLASS,
+            PriorityClass::BelowNormal => BELOW_NORMAL_PRIORITY_CLASS,
+            PriorityClass::Normal => NORMAL_PRIORITY_CLASS,
+            PriorityClass::AboveNormal => ABOVE_NORMAL_PRIORITY_CLASS,
+            PriorityClass::High => HIGH_PRIORITY_CLASS,
+        };
+        with_process(pid, PROCESS_SET_INFORMATION, |handle| unsafe {
+            if SetPriorityClass(handle, class).as_bool() {
+                Ok(())
+            } else {
+                Err(io::Error::last_os_error())
+            }
+        })
+    }
+
+    pub fn set_affinity(pid: u32, cpus: &[usize]) -> io::Result<()> {
+        // The mask only covers the first processor group
+        if cpus.iter().any(|&cpu| cpu >= usize::BITS as usize) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("CPUs above {} are not supported", usize::BITS - 1),
+            ));
+        }
+        let mask = cpus.iter().fold(0usize, |mask, &cpu| mask | (1 << cpu));
+        with_process(pid, PROCESS_SET_INFORMATION, |handle| unsafe {
+            if SetProcessAffinityMask(handle, mask).as_bool() {
+                Ok(())
+            } else {
+                Err(io::Error::last_os_error())
+            }
+        })
+    }
+}
+
+#[cfg(unix)]
+mod imp {
+    use super::PriorityClass;
+    use std::fs;
+    use std::io;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+    pub fn signal(pid: u32, signal: libc::c_int) -> io::Result<()> {
+        let result = unsafe { libc::kill(pid as libc::pid_t, signal) };
+        if result == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+
+    // A zombie has exited; it only waits for its parent to reap it
+    fn is_gone(pid: u32) -> bool {
+        if signal(pid, 0).is_err_and(|e| e.raw_os_error() == Some(libc::ESRCH)) {
+            return true;
+        }
+        fs::read_to_string(format!("/proc/{}/stat", pid))
+            .map(|stat| {
+                // The state follows the parenthesised name, which may itself contain spaces
+                stat.rsplit_once(')')
+                    .and_then(|(_, rest)| rest.split_whitespace().next())
+                    .is_some_and(|state| state == "Z" || state == "X")
+            })
+            .unwrap_or(true)
+    }
+
+    // Whether the process is gone within `timeout`
+    pub fn wait_for_exit(pid: u32, timeout: Duration) -> bool {
+        let started = Instant::now();
+        loop {
+            if is_gone(pid) {
+                return true;
+            }
+            if started.elapsed() >= timeout {
+                return false;
+            }
+            thread::sleep(EXIT_POLL_INTERVAL);
+        }
+    }
+
+    pub fn suspend(pid: u32) -> io::Result<()> {
+        signal(pid, libc::SIGSTOP)
+    }
+
+    pub fn resume(pid: u32) -> io::Result<()> {
+        signal(pid, libc::SIGCONT)
+    }
+
+    // Nice values and affinity are per thread on Linux, so apply them to every thread
+    fn threads(pid: u32) -> Vec<libc::pid_t> {
+        let threads: Vec<libc::pid_t> = fs::read_dir(format!("/proc/{}/task", pid))
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+        if threads.is_empty() {
+            vec![pid as libc::pid_t]
+        } else {
+            threads
+        }
+    }
+
+    pub fn set_priority(pid: u32, priority: PriorityClass) -> io::Result<()> {
+        let nice = match priority {
+            PriorityClass::Idle => 19,
+            PriorityClass::BelowNormal => 10,
+            PriorityClass::Normal => 0,
+            PriorityClass::AboveNormal => -5,
+            PriorityClass::High => -10,
+        };
+        for tid in threads(pid) {
+            let result = unsafe { libc::setpriority(libc::PRIO_PROCESS, tid as libc::id_t, nice) };
+            if result != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+
+    pub fn set_affinity(pid: u32, cpus: &[usize]) -> io::Result<()> {
+        // CPU_SET does not check its index
+        if cpus.iter().any(|&cpu| cpu >= libc::CPU_SETSIZE as usize) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("CPUs above {} are not supported", libc::CPU_SETSIZE - 1),
+            ));
+        }
+        let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+        for &cpu in cpus {
+            unsafe { libc::CPU_SET(cpu, &mut set) };
+        }
+        for tid in threads(pid) {
+            let result = unsafe {
+                libc::sched_setaffinity(tid, std::mem::size_of::<libc::cpu_set_t>(), &set)
+            };
+            if result != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Freezes every thread of the process without losing its state.
+pub fn suspend(pid: u32) -> io::Result<()> {
+    imp::suspend(pid)
+}
+
+/// Continues a process previously frozen with [`suspend`].
+pub fn resume(pid: u32) -> io::Result<()> {
+    imp::resume(pid)
+}
+
+/// Asks the process to exit with SIGTERM and sends SIGKILL if it is still running after a
+/// grace period. Succeeds only once the process is gone.
+#[cfg(unix)]
+pub fn terminate(pid: u32) -> io::Result<()> {
+    // A stopped process would never get to handle SIGTERM
+    imp::signal(pid, libc::SIGCONT)?;
+    imp::signal(pid, libc::SIGTERM)?;
+    if imp::wait_for_exit(pid, TERMINATE_GRACE) {
+        return Ok(());
+    }
+    warn!("Process {} did not exit on SIGTERM, sending SIGKILL", pid);
+    // It may have exited just now
+    if let Err(e) = imp::signal(pid, libc::SIGKILL) {
+        return if e.raw_os_error() == Some(libc::ESRCH) { Ok(()) } else { Err(e) };
+    }
+    if imp::wait_for_exit(pid, KILL_GRACE) {
+        Ok(())
+    } else {
+        Err(io::Error::new(io::ErrorKind::TimedOut, "still running after SIGKILL"))
+    }
+}
+
+/// Sets the Windows priority class, or the equivalent nice value on Linux.
+pub fn set_priority(pid: u32, priority: PriorityClass) -> io::Result<()> {
+    imp::set_priority(pid, priority)
+}
+
+/// Restricts the process to the given logical CPUs.
+pub fn set_affinity(pid: u32, cpus: &[usize]) -> io::Result<()> {
+    if cpus.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "no CPUs selected"));
+    }
+    imp::set_affinity(pid, cpus)
+}
+
+/// Parses a CPU list such as `0,2,4-7`. Returns `None` for malformed input or
+/// CPUs that do not exist on this machine.
+pub fn parse_cpu_list(input: &str) -> Option<Vec<usize>> {
+    let cpu_count = num_cpus::get();
+    let mut cpus = Vec::new();
+    for part in input.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+        match part.split_once('-') {
+            Some((start, end)) => {
+                let start: usize = start.trim().parse().ok()?;
+                let end: usize = end.trim().parse().ok()?;
+                // Checked before expanding, a range like 0-99999999999 would not fit in memory
+                if start > end || end >= cpu_count {
+                    return None;
+                }
+                cpus.extend(start..=end);
+            }
+            None => {
+                let cpu: usize = part.parse().ok()?;
+                if cpu >= cpu_count {
+                    return None;
+                }
+                cpus.push(cpu);
+            }
+        }
+    }
+    cpus.sort_unstable();
+    cpus.dedup();
+    (!cpus.is_empty()).then_some(cpus)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_cpu_lists_and_ranges() {
+        assert_eq!(parse_cpu_list("0"), Some(vec![0]));
+        assert_eq!(parse_cpu_list(" 0 , 0-0,"), Some(vec![0]));
+        if num_cpus::get() >= 4 {
+            assert_eq!(parse_cpu_list("3,0-1,1"), Some(vec![0, 1, 3]));
+        }
+    }
+
+    #[test]
+    fn rejects_bad_cpu_lists() {
+        let missing = num_cpus::get().to_string();
+        let huge_range = format!("0-{}", usize::MAX);
+        for input in ["", ",", "a", "1-0", "0-", "-1", "0-99999999999", missing.as_str(), huge_range.as_str()] {
+            assert_eq!(parse_cpu_list(input), None, "{}", input);
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use serde::{Deserialize, Serialize};
+
+// Processes that must never be ended, suspended or put on a deadline by accident
+#[cfg(windows)]
+const BUILTIN_PROTECTED: &[&str] = &[
+    "system",
+    "smss.exe",
+    "csrss.exe",
+    "wininit.exe",
+    "winlogon.exe",
+    "services.exe",
+    "lsass.exe",
+    "svchost.exe",
+    "dwm.exe",
+    "explorer.exe",
+    "fontdrvhost.exe",
+    "registry",
+];
+
+#[cfg(not(windows))]
+const BUILTIN_PROTECTED: &[&str] = &[
+    "systemd",
+    "init",
+    "kthreadd",
+    "dbus-daemon",
+    "dbus-broker",
+    "Xorg",
+    "Xwayland",
+    "gnome-shell",
+    "plasmashell",
+    "kwin_x11",
+    "kwin_wayland",
+    "sddm",
+    "gdm",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Protection {
+    OwnProcess,
+    System,
+    UserListed,
+}
+
+impl Protection {
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Protection::OwnProcess => "this is TaskTide itself",
+            Protection::System => "critical system process",
+            Protection::UserListed => "on your protected list",
+        }
+    }
+
+    // TaskTide never acts on itself, even with an override
+    pub fn can_override(&self) -> bool {
+        *self != Protection::OwnProcess
+    }
+}
+
+/// Whether TaskTide runs as root, or elevated on Windows. Overrides need it; the daemon
+/// socket only admits its own user, so this covers API clients too.
+#[cfg(unix)]
+pub fn is_elevated() -> bool {
+    unsafe { libc::geteuid() == 0 }
+}
+
+#[cfg(windows)]
+pub fn is_elevated() -> bool {
+    use windows::Win32::Foundation::{CloseHandle, HANDLE};
+    use windows::Win32::Security::{GetTokenInformation, TokenElevation, TOKEN_ELEVATION, TOKEN_QUERY};
+    use windows::Win32::System::Threading::{GetCurrentProcess, OpenProcessToken};
+
+    unsafe {
+        let mut token = HANDLE::default();
+        if !OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token).as_bool() {
+            return false;
+        }
+        let mut elevation = TOKEN_ELEVATION::default();
+        let mut length = 0;
+        let queried = GetTokenInformation(
+            token,
+            TokenElevation,
+            Some(&mut elevation as *mut TOKEN_ELEVATION as *mut _),
+            std::mem::size_of::<TOKEN_ELEVATION>() as u32,
+            &mut length,
+        )
+        .as_bool();
+        let _ = CloseHandle(token);
+        queried && elevation.TokenIsElevated != 0
+    }
+}
+
+pub fn check(pid: u32, process_name: &str, user_list: &[String]) -> Option<Protection> {
+    if pid == std::process::id() {
+        return Some(Protection::OwnProcess);
+    }
+    let matches = |protected: &str| protected.eq_ignore_ascii_case(process_name);
+    if BUILTIN_PROTECTED.iter().any(|&name| matches(name)) {
+        Some(Protection::System)
+    } else if user_list.iter().any(|name| matches(name)) {
+        Some(Protection::UserListed)
+    } else {
+        None
+    }
+}
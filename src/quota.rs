@@ -0,0 +1,115 @@
+use crate::config::project_dirs;
+use chrono::{Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use log::error;
+
+// Gaps longer than this (sleep, hibernation) are not counted as runtime
+const MAX_SAMPLE_GAP: Duration = Duration::from_secs(5);
+const SAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+pub fn budget_key(process_name: &str) -> String {
+    process_name.to_lowercase()
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct DailyUsage {
+    date: Option<NaiveDate>,
+    // Accumulated runtime in seconds, keyed by lowercase executable name
+    seconds: HashMap<String, f64>,
+}
+
+pub struct UsageTracker {
+    usage: DailyUsage,
+    last_sample: Option<Instant>,
+    last_save: Instant,
+}
+
+impl UsageTracker {
+    fn path_for(date: NaiveDate) -> Option<PathBuf> {
+        project_dirs().map(|dirs| {
+            dirs.data_dir()
+                .join("usage")
+                .join(format!("{}.json", date.format("%Y-%m-%d")))
+        })
+    }
+
+    pub fn load() -> Self {
+        let today = Local::now().date_naive();
+        let mut usage: DailyUsage = Self::path_for(today)
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+        usage.date = Some(today);
+
+        Self {
+            usage,
+            last_sample: None,
+            last_save: Instant::now(),
+        }
+    }
+
+    /// Adds the time since the previous sample to every executable in `running`.
+    /// Several instances of the same executable only count once.
+    pub fn record(&mut self, running: &HashSet<String>) {
+        let today = Local::now().date_naive();
+        if self.usage.date != Some(today) {
+            self.save();
+            self.usage = DailyUsage {
+                date: Some(today),
+                seconds: HashMap::new(),
+            };
+        }
+
+        let now = Instant::now();
+        if let Some(last) = self.last_sample {
+            let elapsed = now.duration_since(last).min(MAX_SAMPLE_GAP).as_secs_f64();
+            for name in running {
+                *self.usage.seconds.entry(name.clone()).or_insert(0.0) += elapsed;
+            }
+        }
+        self.last_sample = Some(now);
+
+        if self.last_save.elapsed() >= SAVE_INTERVAL {
+            self.save();
+        }
+    }
+
+    pub fn used(&self, key: &str) -> Duration {
+        Duration::from_secs_f64(self.usage.seconds.get(key).copied().unwrap_or(0.0))
+    }
+
+    pub fn remaining(&self, key: &str, budget_minutes: u32) -> Duration {
+        Duration::from_secs(budget_minutes as u64 * 60).saturating_sub(self.used(key))
+    }
+
+    pub fn save(&mut self) {
+        self.last_save = Instant::now();
+        let Some(path) = self.usage.date.and_then(Self::path_for) else {
+            return;
+        };
+        if let Some(parent) = path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        match serde_json::to_string(&self.usage) {
+            Ok(contents) => {
+                if let Err(e) = fs::write(&path, contents) {
+                    error!("Failed to save usage data: {}", e);
+                }
+            }
+            Err(e) => error!("Failed to serialize usage data: {}", e),
+        }
+    }
+}
+
+impl Drop for UsageTracker {
+    fn drop(&mut self) {
+        // Nothing recorded, so don't overwrite what another instance saved
+        if self.last_sample.is_some() {
+            self.save();
+        }
+    }
+}
@@ -0,0 +1,501 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+
+// Caps on what a process may use; `None` leaves that resource unlimited
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourceLimits {
+    pub memory_bytes: Option<u64>,
+    // Percent of one CPU, so 200 allows two full cores
+    pub cpu_percent: Option<u32>,
+}
+
+impl ResourceLimits {
+    pub fn is_empty(&self) -> bool {
+        self.memory_bytes.is_none() && self.cpu_percent.is_none()
+    }
+}
+
+impl fmt::Display for ResourceLimits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.memory_bytes, self.cpu_percent) {
+            (Some(bytes), Some(percent)) => write!(f, "{} memory, {}% CPU", format_bytes(bytes), percent),
+            (Some(bytes), None) => write!(f, "{} memory", format_bytes(bytes)),
+            (None, Some(percent)) => write!(f, "{}% CPU", percent),
+            (None, None) => write!(f, "none"),
+        }
+    }
+}
+
+/// Parses a memory size such as `512M`, `1.5G` or `1GB`. A bare number is megabytes.
+pub fn parse_memory(input: &str) -> Option<u64> {
+    let input = input.trim().to_ascii_uppercase();
+    let input = input.strip_suffix("IB").or_else(|| input.strip_suffix('B')).unwrap_or(&input);
+    let (number, unit) = match input.find(|c: char| c.is_ascii_alphabetic()) {
+        Some(index) => input.split_at(index),
+        None => (input, "M"),
+    };
+    let unit: u64 = match unit {
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        "T" => 1 << 40,
+        _ => return None,
+    };
+    let number: f64 = number.trim().parse().ok()?;
+    if !number.is_finite() || number <= 0.0 {
+        return None;
+    }
+    Some((number * unit as f64) as u64)
+}
+
+// Every CPU of this machine fully busy
+pub fn max_cpu_percent() -> u32 {
+    u32::try_from(num_cpus::get()).unwrap_or(u32::MAX).saturating_mul(100)
+}
+
+/// Parses a CPU share such as `50` or `150%`, at most 100% per CPU.
+pub fn parse_cpu_percent(input: &str) -> Option<u32> {
+    input.trim().trim_end_matches('%').parse().ok().filter(|&percent| percent > 0 && percent <= max_cpu_percent())
+}
+
+/// Limits as typed into the memory and CPU fields, either of which may be left empty.
+/// `None` while either is invalid.
+pub fn parse_limits(memory: &str, cpu_percent: &str) -> Option<ResourceLimits> {
+    let memory_bytes = match memory.trim() {
+        "" => None,
+        memory => Some(parse_memory(memory)?),
+    };
+    let cpu_percent = match cpu_percent.trim() {
+        "" => None,
+        percent => Some(parse_cpu_percent(percent)?),
+    };
+    Some(ResourceLimits { memory_bytes, cpu_percent })
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    const GB: u64 = 1 << 30;
+    const MB: u64 = 1 << 20;
+    if bytes >= GB && bytes.is_multiple_of(GB / 10) {
+        format!("{} GB", bytes as f64 / GB as f64)
+    } else {
+        format!("{} MB", bytes / MB)
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use super::ResourceLimits;
+    use std::ffi::CString;
+    use std::fs;
+    use std::io;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::MetadataExt;
+    use std::path::{Path, PathBuf};
+    use std::ptr;
+
+    // Our cgroup's own processes move here once it starts holding limited ones, since
+    // cgroup v2 only lets leaves hold processes
+    const MAIN_GROUP: &str = "tasktide-main";
+    // Limited processes each get `tasktide-<pid>` next to it
+    const GROUP_PREFIX: &str = "tasktide-";
+    const CPU_PERIOD_US: u64 = 100_000;
+
+    fn unavailable(reason: impl std::fmt::Display) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("cgroup v2 delegation is not available: {}", reason),
+        )
+    }
+
+    // Failures that mean the cgroup isn't ours to manage become `unavailable`
+    fn write(path: &Path, value: &str) -> io::Result<()> {
+        fs::write(path, value).map_err(|e| match e.raw_os_error() {
+            Some(libc::EACCES) | Some(libc::EPERM) | Some(libc::EROFS) => unavailable(format!(
+                "no write access to {}; run the daemon as a systemd user service with Delegate=yes",
+                path.display(),
+            )),
+            Some(libc::EBUSY) => unavailable(format!("{} holds processes TaskTide cannot move", path.display())),
+            _ => e,
+        })
+    }
+
+    // Where the cgroup v2 hierarchy is mounted; /sys/fs/cgroup unless the system is hybrid
+    fn mount_point() -> io::Result<PathBuf> {
+        let mounts = fs::read_to_string("/proc/self/mountinfo")?;
+        mounts.lines()
+            .find_map(|line| {
+                let (fields, filesystem) = line.split_once(" - ")?;
+                if filesystem.split_whitespace().next()? != "cgroup2" {
+                    return None;
+                }
+                fields.split_whitespace().nth(4).map(PathBuf::from)
+            })
+            .ok_or_else(|| unavailable("no cgroup v2 hierarchy is mounted"))
+    }
+
+    fn create_group(base: &Path, group: &Path) -> io::Result<()> {
+        fs::create_dir(group).map_err(|e| match e.kind() {
+            io::ErrorKind::PermissionDenied => unavailable(format!("cannot create groups in {}", base.display())),
+            _ => e,
+        })
+    }
+
+    // The hierarchy's root and the cgroup TaskTide was started in, under which it keeps
+    // the limited ones
+    fn base() -> io::Result<(PathBuf, PathBuf)> {
+        let root = mount_point()?;
+        let own = fs::read_to_string("/proc/self/cgroup")?;
+        let path = own.lines()
+            .find_map(|line| line.strip_prefix("0::"))
+            .ok_or_else(|| unavailable("TaskTide is not in a cgroup v2 hierarchy"))?;
+        let path = path.trim_start_matches('/');
+        let mut base = if path.is_empty() { root.clone() } else { root.join(path) };
+        if base.file_name().is_some_and(|name| name == MAIN_GROUP) {
+            base.pop();
+        }
+        Ok((root, base))
+    }
+
+    fn has_xattr(path: &Path, name: &str) -> bool {
+        let (Ok(path), Ok(name)) = (CString::new(path.as_os_str().as_bytes()), CString::new(name)) else {
+            return false;
+        };
+        unsafe { libc::getxattr(path.as_ptr(), name.as_ptr(), ptr::null_mut(), 0) >= 0 }
+    }
+
+    // Whether `base` is ours to rearrange. systemd marks groups it delegates with an xattr and
+    // hands them to the service's user; the root group only shows up for init or inside a
+    // container's own cgroup namespace. Writable is not enough, root can write anywhere.
+    fn delegated(root: &Path, base: &Path) -> bool {
+        if base == root || has_xattr(base, "user.delegate") || has_xattr(base, "trusted.delegate") {
+            return true;
+        }
+        let euid = unsafe { libc::geteuid() };
+        euid != 0 && fs::metadata(base).is_ok_and(|metadata| metadata.uid() == euid)
+    }
+
+    // Enables `controllers` for the groups under `base`, first moving its processes into a leaf
+    // unless it is the root, which may hold processes and children at once
+    fn prepare(root: &Path, base: &Path, controllers: &[&str]) -> io::Result<()> {
+        if !delegated(root, base) {
+            return Err(unavailable(format!(
+                "{} was not delegated to TaskTide; run the daemon as a systemd user service with Delegate=yes",
+                base.display(),
+            )));
+        }
+        let available = fs::read_to_string(base.join("cgroup.controllers"))?;
+        if let Some(missing) = controllers.iter().find(|controller| !available.split_whitespace().any(|c| c == **controller)) {
+            return Err(unavailable(format!("the {} controller is not delegated to {}", missing, base.display())));
+        }
+        let enabled = fs::read_to_string(base.join("cgroup.subtree_control"))?;
+        let to_enable: Vec<String> = controllers
+            .iter()
+            .filter(|controller| !enabled.split_whitespace().any(|c| c == **controller))
+            .map(|controller| format!("+{}", controller))
+            .collect();
+        if to_enable.is_empty() {
+            return Ok(());
+        }
+
+        if base != root {
+            let main = base.join(MAIN_GROUP);
+            if !main.exists() {
+                create_group(base, &main)?;
+            }
+            for pid in fs::read_to_string(base.join("cgroup.procs"))?.lines() {
+                // A process may exit while we move it
+                match write(&main.join("cgroup.procs"), pid) {
+                    Err(e) if e.raw_os_error() == Some(libc::ESRCH) => {}
+                    result => result?,
+                }
+            }
+        }
+        write(&base.join("cgroup.subtree_control"), &to_enable.join(" "))
+    }
+
+    // Removes groups whose process has exited
+    fn sweep(base: &Path) {
+        let Ok(entries) = fs::read_dir(base) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name();
+            let Some(name) = name.to_str() else {
+                continue;
+            };
+            if name.starts_with(GROUP_PREFIX) && name != MAIN_GROUP {
+                let empty = fs::read_to_string(entry.path().join("cgroup.procs")).is_ok_and(|procs| procs.trim().is_empty());
+                if empty {
+                    let _ = fs::remove_dir(entry.path());
+                }
+            }
+        }
+    }
+
+    pub fn apply(pid: u32, limits: &ResourceLimits) -> io::Result<()> {
+        let (root, base) = base()?;
+        let group = base.join(format!("{}{}", GROUP_PREFIX, pid));
+        if limits.is_empty() && !group.exists() {
+            return Ok(());
+        }
+
+        let mut controllers = Vec::new();
+        if limits.memory_bytes.is_some() {
+            controllers.push("memory");
+        }
+        if limits.cpu_percent.is_some() {
+            controllers.push("cpu");
+        }
+        prepare(&root, &base, &controllers)?;
+        if !group.exists() {
+            create_group(&base, &group)?;
+        }
+
+        // A file is missing when its controller was never enabled, which means no limit anyway
+        let memory_max = group.join("memory.max");
+        if memory_max.exists() {
+            let value = limits.memory_bytes.map_or("max".to_string(), |bytes| bytes.to_string());
+            write(&memory_max, &value)?;
+        }
+        let cpu_max = group.join("cpu.max");
+        if cpu_max.exists() {
+            let quota = limits.cpu_percent
+                .map_or("max".to_string(), |percent| (CPU_PERIOD_US * percent as u64 / 100).max(1000).to_string());
+            write(&cpu_max, &format!("{} {}", quota, CPU_PERIOD_US))?;
+        }
+        write(&group.join("cgroup.procs"), &pid.to_string())?;
+        sweep(&base);
+        Ok(())
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use super::ResourceLimits;
+    use std::ffi::c_void;
+    use std::io;
+    use std::mem::size_of;
+    use std::sync::Mutex;
+    use windows::core::PCWSTR;
+    use windows::Win32::Foundation::{CloseHandle, HANDLE};
+    use windows::Win32::System::JobObjects::{
+        AssignProcessToJobObject, CreateJobObjectW, JobObjectBasicAccountingInformation,
+        JobObjectCpuRateControlInformation, JobObjectExtendedLimitInformation,
+        QueryInformationJobObject, SetInformationJobObject, JOBOBJECTINFOCLASS,
+        JOBOBJECT_BASIC_ACCOUNTING_INFORMATION, JOBOBJECT_CPU_RATE_CONTROL_INFORMATION, JOBOBJECT_EXTENDED_LIMIT_INFORMATION,
+        JOB_OBJECT_CPU_RATE_CONTROL_ENABLE, JOB_OBJECT_CPU_RATE_CONTROL_HARD_CAP,
+        JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE, JOB_OBJECT_LIMIT_PROCESS_MEMORY,
+    };
+    use windows::Win32::System::Threading::{OpenProcess, PROCESS_SET_QUOTA, PROCESS_TERMINATE};
+
+    // A job holding one process. It stays open until the process exits, since closing it
+    // lifts the limits, or ends the process when it is kill-on-close
+    struct Job {
+        pid: u32,
+        handle: HANDLE,
+        kill_on_close: bool,
+        limits: ResourceLimits,
+    }
+
+    static JOBS: Mutex<Vec<Job>> = Mutex::new(Vec::new());
+
+    // HRESULT_FROM_WIN32 keeps the Win32 error code in the low word
+    fn win32_error(error: windows::core::Error) -> io::Error {
+        io::Error::from_raw_os_error(error.code().0 & 0xFFFF)
+    }
+
+    fn set_information<T>(job: HANDLE, class: JOBOBJECTINFOCLASS, information: &T) -> io::Result<()> {
+        let set = unsafe {
+            SetInformationJobObject(job, class, information as *const T as *const c_void, size_of::<T>() as u32)
+        };
+        if set.as_bool() {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+
+    // A job whose process has exited limits nothing and only holds on to its handle
+    fn is_empty(job: &Job) -> bool {
+        let mut accounting = JOBOBJECT_BASIC_ACCOUNTING_INFORMATION::default();
+        let queried = unsafe {
+            QueryInformationJobObject(
+                job.handle,
+                JobObjectBasicAccountingInformation,
+                &mut accounting as *mut _ as *mut c_void,
+                size_of::<JOBOBJECT_BASIC_ACCOUNTING_INFORMATION>() as u32,
+                None,
+            )
+        };
+        queried.as_bool() && accounting.ActiveProcesses == 0
+    }
+
+    fn prune(jobs: &mut Vec<Job>) {
+        jobs.retain(|job| {
+            if !is_empty(job) {
+                return true;
+            }
+            unsafe {
+                let _ = CloseHandle(job.handle);
+            }
+            false
+        });
+    }
+
+    // Puts the process in a new job, or finds the one it is already in
+    fn job_for(jobs: &mut Vec<Job>, pid: u32) -> io::Result<&mut Job> {
+        if let Some(index) = jobs.iter().position(|job| job.pid == pid) {
+            return Ok(&mut jobs[index]);
+        }
+        unsafe {
+            let handle = CreateJobObjectW(None, PCWSTR::null()).map_err(win32_error)?;
+            let process = match OpenProcess(PROCESS_SET_QUOTA | PROCESS_TERMINATE, false, pid) {
+                Ok(process) => process,
+                Err(e) => {
+                    let _ = CloseHandle(handle);
+                    return Err(win32_error(e));
+                }
+            };
+            let assigned = AssignProcessToJobObject(handle, process).as_bool();
+            let error = io::Error::last_os_error();
+            let _ = CloseHandle(process);
+            if !assigned {
+                let _ = CloseHandle(handle);
+                return Err(error);
+            }
+        }
+        jobs.push(Job { pid, handle, kill_on_close: false, limits: ResourceLimits::default() });
+        Ok(jobs.last_mut().unwrap())
+    }
+
+    fn update(job: &Job) -> io::Result<()> {
+        let mut limit = JOBOBJECT_EXTENDED_LIMIT_INFORMATION::default();
+        if job.kill_on_close {
+            limit.BasicLimitInformation.LimitFlags |= JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+        }
+        if let Some(bytes) = job.limits.memory_bytes {
+            limit.BasicLimitInformation.LimitFlags |= JOB_OBJECT_LIMIT_PROCESS_MEMORY;
+            limit.ProcessMemoryLimit = bytes as usize;
+        }
+        set_information(job.handle, JobObjectExtendedLimitInformation, &limit)?;
+
+        let mut rate = JOBOBJECT_CPU_RATE_CONTROL_INFORMATION::default();
+        if let Some(percent) = job.limits.cpu_percent {
+            rate.ControlFlags = JOB_OBJECT_CPU_RATE_CONTROL_ENABLE | JOB_OBJECT_CPU_RATE_CONTROL_HARD_CAP;
+            // In hundredths of a percent of all processors together
+            let cpus = u32::try_from(num_cpus::get()).unwrap_or(u32::MAX);
+            rate.Anonymous.CpuRate = percent
+                .checked_mul(100)
+                .map_or(10_000, |hundredths| hundredths / cpus)
+                .clamp(1, 10_000);
+        }
+        set_information(job.handle, JobObjectCpuRateControlInformation, &rate)
+    }
+
+    pub fn release_exited() {
+        prune(&mut JOBS.lock().unwrap());
+    }
+
+    pub fn apply(pid: u32, limits: &ResourceLimits) -> io::Result<()> {
+        let mut jobs = JOBS.lock().unwrap();
+        prune(&mut jobs);
+        if limits.is_empty() && !jobs.iter().any(|job| job.pid == pid) {
+            return Ok(());
+        }
+        let job = job_for(&mut jobs, pid)?;
+        job.limits = *limits;
+        update(job)
+    }
+
+    pub fn kill_on_close(pid: u32) -> io::Result<()> {
+        let mut jobs = JOBS.lock().unwrap();
+        prune(&mut jobs);
+        let job = job_for(&mut jobs, pid)?;
+        job.kill_on_close = true;
+        update(job)
+    }
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+mod imp {
+    use super::ResourceLimits;
+    use std::io;
+
+    pub fn apply(_pid: u32, _limits: &ResourceLimits) -> io::Result<()> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "resource limits are not supported on this platform"))
+    }
+}
+
+/// Caps the process's memory and CPU; empty limits lift the caps again. On Linux the
+/// process moves into a cgroup v2 group TaskTide manages under its own, on Windows into
+/// a Job Object.
+pub fn apply(pid: u32, limits: &ResourceLimits) -> io::Result<()> {
+    // Limits also come from API clients, which `parse_cpu_percent` never saw
+    if let Some(percent) = limits.cpu_percent.filter(|&percent| percent == 0 || percent > max_cpu_percent()) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("a CPU limit of {}% is not between 1% and {}%", percent, max_cpu_percent()),
+        ));
+    }
+    imp::apply(pid, limits)
+}
+
+/// Closes the Job Objects of processes that have exited.
+#[cfg(windows)]
+pub fn release_exited() {
+    imp::release_exited()
+}
+
+/// Ends the process when TaskTide exits, for programs it launched.
+#[cfg(windows)]
+pub fn kill_on_close(pid: u32) -> io::Result<()> {
+    imp::kill_on_close(pid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_memory_with_units() {
+        assert_eq!(parse_memory("512"), Some(512 << 20));
+        assert_eq!(parse_memory("512M"), Some(512 << 20));
+        assert_eq!(parse_memory("256 kb"), Some(256 << 10));
+        assert_eq!(parse_memory("1.5G"), Some(3 << 29));
+        assert_eq!(parse_memory("2GiB"), Some(2 << 30));
+        assert_eq!(parse_memory(" 1t "), Some(1 << 40));
+    }
+
+    #[test]
+    fn rejects_bad_memory() {
+        for input in ["", "0", "-1", "0.0G", "lots", "G", "5X", "NaN", "inf"] {
+            assert_eq!(parse_memory(input), None, "{}", input);
+        }
+    }
+
+    #[test]
+    fn parses_cpu_percent() {
+        assert_eq!(parse_cpu_percent("50"), Some(50));
+        assert_eq!(parse_cpu_percent(" 80% "), Some(80));
+        assert_eq!(parse_cpu_percent("0"), None);
+        assert_eq!(parse_cpu_percent("-5"), None);
+        assert_eq!(parse_cpu_percent("half"), None);
+        assert_eq!(parse_cpu_percent(&max_cpu_percent().to_string()), Some(max_cpu_percent()));
+        assert_eq!(parse_cpu_percent(&(max_cpu_percent() + 1).to_string()), None);
+        assert_eq!(parse_cpu_percent("4294967295"), None);
+    }
+
+    #[test]
+    fn empty_limit_fields_are_unset() {
+        assert_eq!(
+            parse_limits("", "").map(|limits| (limits.memory_bytes, limits.cpu_percent)),
+            Some((None, None))
+        );
+        assert_eq!(
+            parse_limits("1G", "").map(|limits| (limits.memory_bytes, limits.cpu_percent)),
+            Some((Some(1 << 30), None))
+        );
+        assert!(parse_limits("1G", "none").is_none());
+    }
+}
@@ -0,0 +1,206 @@
+// JSON-RPC 2.0 front end for other tools, served on the daemon socket next to
+// the GUI protocol. Methods are translated into the same `Request`s the GUI sends.
+use crate::daemon::{self, Request, Response};
+use crate::error::BackendError;
+use crate::scheduler::{Event, Scheduler};
+use crate::task::{self, DeadlineAction, TimeInterval};
+use chrono::{DateTime, Local};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Mutex;
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+// Server-defined range: the request was fine but the operation did not succeed
+pub const OPERATION_FAILED: i64 = -32000;
+pub const NO_SUCH_PROCESS: i64 = -32001;
+
+#[derive(Deserialize)]
+struct Call {
+    jsonrpc: String,
+    // Calls without an id are notifications and get no reply
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self { code, message: message.into() }
+    }
+}
+
+#[derive(Deserialize)]
+struct PidParams {
+    pid: u32,
+}
+
+#[derive(Deserialize)]
+struct DeadlineParams {
+    pid: u32,
+    // Either minutes from now or an RFC 3339 timestamp
+    minutes: Option<i64>,
+    at: Option<DateTime<Local>>,
+    action: Option<DeadlineAction>,
+}
+
+#[derive(Deserialize)]
+struct TerminateParams {
+    pid: u32,
+    #[serde(default = "default_save_first")]
+    save_first: bool,
+    #[serde(default)]
+    tree: bool,
+}
+
+fn default_save_first() -> bool {
+    true
+}
+
+/// What the connection should do after a call.
+pub struct Reply {
+    pub response: Option<Value>,
+    pub subscribe: bool,
+}
+
+/// True when a line from a client is meant for this API rather than the GUI protocol.
+pub fn is_rpc(line: &str) -> bool {
+    match serde_json::from_str::<Value>(line) {
+        Ok(value) => value.get("jsonrpc").is_some(),
+        // Only JSON-RPC clients get a JSON-RPC parse error back
+        Err(_) => line.contains("\"jsonrpc\""),
+    }
+}
+
+pub fn handle(scheduler: &Mutex<Scheduler>, line: &str) -> Reply {
+    let call: Call = match serde_json::from_str::<Value>(line) {
+        Err(e) => return error_reply(Value::Null, RpcError::new(PARSE_ERROR, e.to_string())),
+        Ok(value) => match serde_json::from_value(value) {
+            Ok(call) => call,
+            Err(e) => return error_reply(Value::Null, RpcError::new(INVALID_REQUEST, e.to_string())),
+        },
+    };
+    if call.jsonrpc != "2.0" {
+        return error_reply(call.id.unwrap_or(Value::Null), RpcError::new(INVALID_REQUEST, "jsonrpc must be \"2.0\""));
+    }
+
+    let subscribe = call.method == "subscribe";
+    let result = dispatch(scheduler, &call.method, call.params);
+    let response = call.id.map(|id| match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(error) => error_response(id, error),
+    });
+    Reply { response, subscribe }
+}
+
+fn error_response(id: Value, error: RpcError) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": error.code, "message": error.message },
+    })
+}
+
+fn error_reply(id: Value, error: RpcError) -> Reply {
+    Reply {
+        response: Some(error_response(id, error)),
+        subscribe: false,
+    }
+}
+
+/// Notification pushed to subscribed connections.
+pub fn notification(event: &Event) -> Value {
+    json!({ "jsonrpc": "2.0", "method": "event", "params": event })
+}
+
+fn params<T: for<'de> Deserialize<'de>>(params: Value) -> Result<T, RpcError> {
+    serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))
+}
+
+fn to_value(value: impl serde::Serialize) -> Result<Value, RpcError> {
+    serde_json::to_value(value).map_err(|e| RpcError::new(OPERATION_FAILED, e.to_string()))
+}
+
+fn failure(error: BackendError) -> RpcError {
+    match error {
+        BackendError::NoSuchProcess { .. } => RpcError::new(NO_SUCH_PROCESS, error.to_string()),
+        _ => RpcError::new(OPERATION_FAILED, error.to_string()),
+    }
+}
+
+fn execute(scheduler: &mut Scheduler, request: Request) -> Result<Value, RpcError> {
+    match daemon::handle(scheduler, request) {
+        Response::Error(e) => Err(failure(e)),
+        _ => Ok(Value::Bool(true)),
+    }
+}
+
+pub fn dispatch(scheduler: &Mutex<Scheduler>, method: &str, raw_params: Value) -> Result<Value, RpcError> {
+    // Ending processes waits on before-hooks, which must not hold the scheduler
+    if method == "terminate" {
+        return terminate(scheduler, raw_params);
+    }
+    let scheduler = &mut *daemon::lock(scheduler);
+    match method {
+        "list_processes" => {
+            let mut tasks = scheduler.snapshot().tasks;
+            tasks.sort_by_key(|task| task.pid);
+            to_value(tasks)
+        }
+        "get_process" => {
+            let PidParams { pid } = params(raw_params)?;
+            let task = scheduler.snapshot()
+                .tasks
+                .into_iter()
+                .find(|task| task.pid == pid)
+                .ok_or_else(|| RpcError::new(NO_SUCH_PROCESS, format!("no process with PID {}", pid)))?;
+            to_value(task)
+        }
+        "set_deadline" => {
+            let DeadlineParams { pid, minutes, at, action } = params(raw_params)?;
+            let deadline = match (minutes, at) {
+                (Some(minutes), None) => {
+                    let length = task::minutes_duration(minutes)
+                        .ok_or_else(|| RpcError::new(INVALID_PARAMS, format!("a deadline of {} minutes is not between 1 minute and a year", minutes)))?;
+                    Local::now() + length
+                }
+                (None, Some(at)) => at,
+                _ => return Err(RpcError::new(INVALID_PARAMS, "pass exactly one of minutes or at")),
+            };
+            execute(scheduler, Request::SetDeadline(pid, TimeInterval::Custom(deadline)))?;
+            if let Some(action) = action {
+                execute(scheduler, Request::SetDeadlineAction(pid, action))?;
+            }
+            to_value(deadline)
+        }
+        "clear_deadline" => {
+            let PidParams { pid } = params(raw_params)?;
+            execute(scheduler, Request::ClearDeadline(pid))
+        }
+        // Registration happens on the connection; events arrive as `event` notifications
+        "subscribe" => Ok(Value::Bool(true)),
+        _ => Err(RpcError::new(METHOD_NOT_FOUND, format!("unknown method '{}'", method))),
+    }
+}
+
+fn terminate(scheduler: &Mutex<Scheduler>, raw_params: Value) -> Result<Value, RpcError> {
+    let TerminateParams { pid, save_first, tree } = params(raw_params)?;
+    let mut victims = Vec::new();
+    if tree {
+        let tasks = daemon::lock(scheduler).snapshot().tasks;
+        victims.extend(task::descendants(tasks.iter(), pid).into_iter().map(|(pid, _)| pid));
+    }
+    victims.push(pid);
+    for victim in victims {
+        daemon::terminate(scheduler, victim, save_first).map_err(failure)?;
+    }
+    Ok(Value::Bool(true))
+}
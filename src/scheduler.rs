@@ -0,0 +1,1169 @@
+use crate::audit::{self, Action, AuditEntry, Outcome, SmartSave, Trigger};
+use crate::config::{Config, ProcessRule, TerminationHook, CONFIG_VERSION};
+use crate::error::BackendError;
+use crate::focus::FocusSession;
+use crate::hooks;
+use crate::launch;
+use crate::process_control::{self, PriorityClass};
+use crate::protection::{self, Protection};
+use crate::quota::{budget_key, UsageTracker};
+use crate::resource_limits::{self, ResourceLimits};
+use crate::smart_save::{self, SaveCheck, SaveState};
+use crate::task::{DeadlineAction, ProcessStatus, Task, TimeInterval};
+use crate::watchdog::{Lost, Restart, Watchdog};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::PathBuf;
+use std::process::Child;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use sysinfo::{Pid, PidExt, ProcessExt, System, SystemExt};
+#[cfg(windows)]
+use std::io;
+#[cfg(windows)]
+use windows::Win32::Foundation::{CloseHandle, HANDLE};
+#[cfg(windows)]
+use windows::Win32::System::Threading::{OpenProcess, TerminateProcess, PROCESS_ACCESS_RIGHTS, PROCESS_TERMINATE};
+use log::{error, info, warn};
+
+// How long automatic enforcement leaves a process alone after a hook or unsaved work stopped its kill
+const REFUSAL_COOLDOWN: Duration = Duration::from_secs(60);
+// Most ended processes kept for relaunching
+const MAX_RECENTLY_ENDED: usize = 20;
+
+// Everything a front end needs to draw one refresh
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub tasks: Vec<Task>,
+    pub focus: Option<FocusSession>,
+    pub config: Config,
+    // Newest first
+    pub recently_ended: Vec<RecentlyEnded>,
+}
+
+// A process TaskTide ended that can still be relaunched
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecentlyEnded {
+    pub pid: u32,
+    pub name: String,
+    pub ended_at: DateTime<Local>,
+}
+
+// Something the scheduler did, streamed to subscribed API clients
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    DeadlineSet { pid: u32, name: String, deadline: DateTime<Local> },
+    DeadlineCleared { pid: u32 },
+    DeadlineReached { pid: u32, name: String, action: DeadlineAction },
+    ProcessTerminated { pid: u32, name: String },
+    ProcessSuspended { pid: u32 },
+    ProcessResumed { pid: u32 },
+    ProcessExited { pid: u32, name: String, status: String },
+    ProcessRestarted { pid: u32, new_pid: u32, name: String, attempt: u32 },
+    ProcessRelaunched { pid: u32, new_pid: u32, name: String },
+    FocusStarted { ends_at: DateTime<Local> },
+    FocusEnded,
+}
+
+// What it takes to start an ended process again, captured before the kill
+struct Ended {
+    pid: u32,
+    name: String,
+    exe: String,
+    command: Vec<String>,
+    cwd: PathBuf,
+    env: Vec<(String, String)>,
+    ended_at: DateTime<Local>,
+}
+
+// A process started by `Scheduler::launch`
+struct Launched {
+    child: Child,
+    // User for launches, Watchdog for restarts
+    trigger: Trigger,
+    started: DateTime<Local>,
+    command: Vec<String>,
+}
+
+// Before-hooks of one termination. They can take seconds, so callers run them with the
+// scheduler unlocked and pass the verdict to `terminate_process`.
+pub struct BeforeHooks {
+    pid: u32,
+    name: String,
+    // Command, timeout and whether a failure stops the termination
+    hooks: Vec<(String, Duration, bool)>,
+    env: Vec<(&'static str, String)>,
+}
+
+impl BeforeHooks {
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    /// Runs the hooks in order and returns the command of the first one that vetoed.
+    pub fn run(&self) -> Option<String> {
+        for (command, timeout, can_veto) in &self.hooks {
+            match hooks::run(command, &self.env, *timeout) {
+                Ok(status) if !status.success() && *can_veto => {
+                    warn!("Not ending {} (PID {}): hook `{}` exited with {}", self.name, self.pid, command, status);
+                    return Some(command.clone());
+                }
+                Ok(_) => {}
+                // A hook that can't run or hangs is not a veto
+                Err(e) => warn!("Hook `{}` before ending {} failed: {}", command, self.name, e),
+            }
+        }
+        None
+    }
+}
+
+// PID, trigger and vetoing hook of before-hooks run for an automatic termination
+type Verdict = (u32, Trigger, Option<String>);
+
+// Running totals for the metrics endpoint
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Stats {
+    pub terminations: u64,
+    pub termination_failures: u64,
+    pub smart_save_attempts: u64,
+    pub smart_save_failures: u64,
+    // How long the last process list refresh took
+    pub sample_duration: Duration,
+}
+
+// Owns the process list and enforces deadlines, budgets, focus sessions and rules.
+// Runs inside the GUI or headless in the daemon; both drive it through `tick`.
+pub struct Scheduler {
+    system: System,
+    tasks: HashMap<u32, Task>,
+    config: Config,
+    usage: UsageTracker,
+    focus: Option<FocusSession>,
+    suspended: HashSet<u32>,
+    // PIDs that already had their app's saved priority/affinity rule applied
+    rules_applied: HashSet<u32>,
+    // PIDs the user explicitly allowed despite their protection
+    protection_overrides: HashSet<u32>,
+    events: Vec<Event>,
+    stats: Stats,
+    // PIDs we ended that may still show up until they are reaped
+    terminated: HashSet<u32>,
+    // PIDs that could not be ended; shown as Failed while they run
+    termination_failed: HashSet<u32>,
+    // Processes started by `launch`, reaped when they exit
+    launched: HashMap<u32, Launched>,
+    watchdog: Watchdog,
+    // Newest last
+    recently_ended: VecDeque<Ended>,
+    // When a hook, unsaved work or an error last stopped a kill, so enforcement doesn't retry every tick
+    refused: HashMap<u32, Instant>,
+    // Deadline each PID was last reported for, so a refused deadline is only announced once
+    announced_deadlines: HashMap<u32, DateTime<Local>>,
+    // PIDs due to be ended whose before-hooks are still running on their own thread
+    awaiting_hooks: HashSet<u32>,
+    verdict_sender: Sender<Verdict>,
+    verdicts: Receiver<Verdict>,
+    // Modification time of the config file we last read or wrote
+    config_modified: Option<SystemTime>,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        let (verdict_sender, verdicts) = mpsc::channel();
+        Self {
+            system: System::new_all(),
+            tasks: HashMap::new(),
+            config: Config::load(),
+            usage: UsageTracker::load(),
+            focus: None,
+            suspended: HashSet::new(),
+            rules_applied: HashSet::new(),
+            protection_overrides: HashSet::new(),
+            events: Vec::new(),
+            stats: Stats::default(),
+            terminated: HashSet::new(),
+            termination_failed: HashSet::new(),
+            launched: HashMap::new(),
+            watchdog: Watchdog::default(),
+            recently_ended: VecDeque::new(),
+            refused: HashMap::new(),
+            announced_deadlines: HashMap::new(),
+            awaiting_hooks: HashSet::new(),
+            verdict_sender,
+            verdicts,
+            config_modified: Config::modified(),
+        }
+    }
+
+    // Events since the last call, oldest first
+    pub fn drain_events(&mut self) -> Vec<Event> {
+        std::mem::take(&mut self.events)
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            tasks: self.tasks.values().cloned().collect(),
+            focus: self.focus.clone(),
+            config: self.config.clone(),
+            recently_ended: self.recently_ended
+                .iter()
+                .rev()
+                .map(|ended| RecentlyEnded { pid: ended.pid, name: ended.name.clone(), ended_at: ended.ended_at })
+                .collect(),
+        }
+    }
+
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    // Re-reads the process list without enforcing anything
+    pub fn refresh(&mut self) {
+        let started = Instant::now();
+        self.system.refresh_all();
+
+        let mut updated_tasks = HashMap::new();
+        for process in self.system.processes().values() {
+            let pid = process.pid().as_u32();
+            let name = process.name().to_string();
+            let budget_remaining = self.config.daily_budgets
+                .get(&budget_key(&name))
+                .map(|&minutes| self.usage.remaining(&budget_key(&name), minutes));
+
+            let mut task = match self.tasks.remove(&pid) {
+                Some(existing_task) => existing_task,
+                None => Task::new(name.clone(), pid, 0.0, 0),
+            };
+            task.exe = process.exe().to_string_lossy().into_owned();
+            task.parent = process.parent().map(|parent| parent.as_u32());
+            task.start_time = process.start_time();
+            task.cpu_usage = process.cpu_usage();
+            task.memory_usage = process.memory();
+            task.budget_remaining = budget_remaining;
+            task.protection = protection::check(pid, &name, &self.config.protected_processes);
+            task.protection_overridden = self.protection_overrides.contains(&pid);
+            task.status = if self.terminated.contains(&pid) {
+                ProcessStatus::Terminated
+            } else if self.termination_failed.contains(&pid) {
+                ProcessStatus::Failed
+            } else if self.suspended.contains(&pid) {
+                ProcessStatus::Suspended
+            } else {
+                task.get_status()
+            };
+            updated_tasks.insert(pid, task);
+        }
+        self.tasks = updated_tasks;
+
+        // Forget processes that have exited
+        let system = &self.system;
+        self.suspended.retain(|&pid| system.process(Pid::from_u32(pid)).is_some());
+        self.rules_applied.retain(|&pid| system.process(Pid::from_u32(pid)).is_some());
+        self.protection_overrides.retain(|&pid| system.process(Pid::from_u32(pid)).is_some());
+        self.terminated.retain(|&pid| system.process(Pid::from_u32(pid)).is_some());
+        self.termination_failed.retain(|&pid| system.process(Pid::from_u32(pid)).is_some());
+        self.refused.retain(|&pid, _| system.process(Pid::from_u32(pid)).is_some());
+        self.announced_deadlines.retain(|&pid, _| system.process(Pid::from_u32(pid)).is_some());
+        self.awaiting_hooks.retain(|&pid| system.process(Pid::from_u32(pid)).is_some());
+        #[cfg(windows)]
+        resource_limits::release_exited();
+        self.stats.sample_duration = started.elapsed();
+    }
+
+    pub fn tick(&mut self) {
+        self.reload_config();
+        self.reap_launched();
+        self.refresh();
+
+        let window = chrono::Duration::minutes(self.config.relaunch_window_minutes as i64);
+        self.recently_ended.retain(|ended| Local::now() - ended.ended_at < window);
+
+        // Track daily runtime of every running executable
+        let running: HashSet<String> = self.system.processes()
+            .values()
+            .map(|process| budget_key(process.name()))
+            .collect();
+        self.usage.record(&running);
+
+        // Apply saved priority/affinity rules to newly started processes
+        let mut new_rules = Vec::new();
+        for process in self.system.processes().values() {
+            let pid = process.pid().as_u32();
+            if self.rules_applied.insert(pid) {
+                if let Some(rule) = self.config.process_rules.get(&budget_key(process.name())) {
+                    new_rules.push((pid, rule.clone()));
+                }
+            }
+        }
+        for (pid, rule) in new_rules {
+            if let Some(priority) = rule.priority {
+                let _ = self.set_priority(pid, priority);
+            }
+            if let Some(cpus) = &rule.affinity {
+                let _ = self.set_affinity(pid, cpus);
+            }
+        }
+
+        self.run_watchdog(&running);
+
+        // Check for deadline reached
+        let now = Local::now();
+        let mut to_terminate = Vec::new();
+        let mut to_suspend = Vec::new();
+        let mut to_deprioritize = Vec::new();
+        for (&pid, task) in &self.tasks {
+            if let Some(deadline) = task.deadline {
+                if now > deadline {
+                    if self.announced_deadlines.insert(pid, deadline) != Some(deadline) {
+                        self.events.push(Event::DeadlineReached {
+                            pid,
+                            name: task.name.clone(),
+                            action: task.deadline_action,
+                        });
+                    }
+                    match task.deadline_action {
+                        DeadlineAction::Terminate => to_terminate.push((pid, Trigger::Deadline)),
+                        DeadlineAction::Suspend => to_suspend.push((pid, Trigger::Deadline)),
+                        DeadlineAction::LowerPriority => to_deprioritize.push(pid),
+                    }
+                }
+            }
+        }
+
+        // Budgets and focus sessions silently leave protected processes alone
+        let protected: HashSet<u32> = self.tasks
+            .values()
+            .filter(|task| task.effective_protection().is_some())
+            .map(|task| task.pid)
+            .collect();
+
+        // End every instance of an app whose daily budget is exhausted, including relaunches
+        for process in self.system.processes().values() {
+            let key = budget_key(process.name());
+            if let Some(&minutes) = self.config.daily_budgets.get(&key) {
+                let pid = process.pid().as_u32();
+                if self.usage.remaining(&key, minutes).is_zero()
+                    && !to_terminate.iter().any(|&(queued, _)| queued == pid)
+                    && !protected.contains(&pid)
+                {
+                    info!("Daily budget exhausted for {}", process.name());
+                    to_terminate.push((pid, Trigger::DailyBudget));
+                }
+            }
+        }
+
+        // End the focus session once its timer runs out, otherwise block listed apps
+        if self.focus.as_ref().is_some_and(|session| !session.is_active()) {
+            info!("Focus session finished");
+            self.end_focus_session();
+        }
+        if let Some(session) = &mut self.focus {
+            for process in self.system.processes().values() {
+                let pid = process.pid().as_u32();
+                if !session.blocks(process.name())
+                    || to_terminate.iter().any(|&(queued, _)| queued == pid)
+                    || protected.contains(&pid)
+                {
+                    continue;
+                }
+                // Focus sessions only ever end or freeze blocked apps
+                match session.action {
+                    DeadlineAction::Terminate | DeadlineAction::LowerPriority => {
+                        info!("Blocked {} during focus session", process.name());
+                        to_terminate.push((pid, Trigger::FocusSession));
+                    }
+                    DeadlineAction::Suspend => {
+                        if !self.suspended.contains(&pid) {
+                            info!("Suspending {} during focus session", process.name());
+                            session.suspended.insert(pid);
+                            to_suspend.push((pid, Trigger::FocusSession));
+                        }
+                    }
+                }
+            }
+        }
+
+        // Suspend or deprioritize tasks whose deadline asks for it; the deadline is consumed once applied
+        for (pid, trigger) in to_suspend {
+            let started = Local::now();
+            let result = self.suspend_process(pid);
+            self.audit(started, trigger, Action::Suspend, pid, SmartSave::NotAttempted, outcome(&result));
+            if let Some(task) = self.tasks.get_mut(&pid) {
+                task.deadline = None;
+            }
+        }
+        for pid in to_deprioritize {
+            let started = Local::now();
+            let result = self.set_priority(pid, PriorityClass::Idle);
+            self.audit(started, Trigger::Deadline, Action::LowerPriority, pid, SmartSave::NotAttempted, outcome(&result));
+            if let Some(task) = self.tasks.get_mut(&pid) {
+                task.deadline = None;
+            }
+        }
+
+        // Terminate tasks that reached their deadline, budget or focus block. Before-hooks run
+        // on their own thread and the process is ended on a later tick if it is still due.
+        let mut verdicts = HashMap::new();
+        for (pid, trigger, vetoed_by) in self.verdicts.try_iter() {
+            self.awaiting_hooks.remove(&pid);
+            verdicts.insert(pid, (trigger, vetoed_by));
+        }
+        for (pid, trigger) in to_terminate {
+            if self.refused.get(&pid).is_some_and(|at| at.elapsed() < REFUSAL_COOLDOWN) {
+                continue;
+            }
+            match verdicts.remove(&pid) {
+                Some((checked, vetoed_by)) if checked == trigger => {
+                    let _ = self.terminate_process(pid, true, trigger, vetoed_by);
+                }
+                _ if self.awaiting_hooks.contains(&pid) => {}
+                _ => {
+                    let hooks = self.before_hooks(pid, trigger);
+                    if hooks.is_empty() {
+                        let _ = self.terminate_process(pid, true, trigger, None);
+                        continue;
+                    }
+                    self.awaiting_hooks.insert(pid);
+                    let sender = self.verdict_sender.clone();
+                    thread::spawn(move || {
+                        let _ = sender.send((pid, trigger, hooks.run()));
+                    });
+                }
+            }
+        }
+    }
+
+    /// Applies `pid`'s deadline once it has passed, leaving every other process, budget and
+    /// the usage log alone. For `tasktide run` when no daemon is enforcing.
+    pub fn enforce_deadline(&mut self, pid: u32) {
+        self.refresh();
+        let Some(task) = self.tasks.get(&pid) else {
+            return;
+        };
+        if task.deadline.is_none_or(|deadline| Local::now() <= deadline) {
+            return;
+        }
+        let started = Local::now();
+        match task.deadline_action {
+            DeadlineAction::Terminate => {
+                if self.refused.get(&pid).is_some_and(|at| at.elapsed() < REFUSAL_COOLDOWN) {
+                    return;
+                }
+                // Nothing else waits on this process, so its before-hooks run right here
+                let vetoed_by = self.before_hooks(pid, Trigger::Deadline).run();
+                let _ = self.terminate_process(pid, true, Trigger::Deadline, vetoed_by);
+                return;
+            }
+            DeadlineAction::Suspend => {
+                let result = self.suspend_process(pid);
+                self.audit(started, Trigger::Deadline, Action::Suspend, pid, SmartSave::NotAttempted, outcome(&result));
+            }
+            DeadlineAction::LowerPriority => {
+                let result = self.set_priority(pid, PriorityClass::Idle);
+                self.audit(started, Trigger::Deadline, Action::LowerPriority, pid, SmartSave::NotAttempted, outcome(&result));
+            }
+        }
+        if let Some(task) = self.tasks.get_mut(&pid) {
+            task.deadline = None;
+        }
+    }
+
+    // Picks up edits to the config file made by hand or by another TaskTide process
+    fn reload_config(&mut self) {
+        let modified = Config::modified();
+        if modified == self.config_modified {
+            return;
+        }
+        self.config_modified = modified;
+        match Config::read() {
+            Ok(config) => {
+                info!("Reloaded configuration");
+                self.config = config;
+            }
+            Err(e) => warn!("Keeping the current configuration, the config file is invalid: {}", e),
+        }
+    }
+
+    fn persist_config(&mut self) {
+        self.config.save();
+        self.config_modified = Config::modified();
+    }
+
+    // Replaces every setting at once, as the settings page does
+    pub fn save_config(&mut self, config: Config) {
+        self.config = Config { version: CONFIG_VERSION, ..config };
+        self.persist_config();
+        info!("Configuration saved");
+    }
+
+    // Protection of a live process, unless the user overrode it for this PID
+    pub fn protection(&self, pid: u32) -> Option<Protection> {
+        let process = self.system.process(Pid::from_u32(pid))?;
+        protection::check(pid, process.name(), &self.config.protected_processes)
+            .filter(|protection| !(protection.can_override() && self.protection_overrides.contains(&pid)))
+    }
+
+    pub fn set_deadline(&mut self, pid: u32, interval: TimeInterval) -> Result<(), BackendError> {
+        // The process may have started since the last tick
+        if !self.tasks.contains_key(&pid) {
+            self.refresh();
+        }
+        if let Some(protection) = self.protection(pid) {
+            warn!("Refusing to set a deadline on process {}: {}", pid, protection.reason());
+            return Err(BackendError::Protected { pid, reason: protection.reason().to_string() });
+        }
+        let task = self.tasks.get_mut(&pid).ok_or(BackendError::NoSuchProcess { pid })?;
+        let deadline = interval.deadline();
+        task.deadline = Some(deadline);
+        self.events.push(Event::DeadlineSet { pid, name: task.name.clone(), deadline });
+        Ok(())
+    }
+
+    /// Starts `command` with no terminal, ending it at `limit` like any other deadline.
+    pub fn launch(&mut self, command: Vec<String>, limit: Option<TimeInterval>) -> Result<u32, BackendError> {
+        let started = Local::now();
+        let child = launch::spawn(&command, None, &[], false).map_err(|e| BackendError::LaunchFailed {
+            command: launch::program_name(&command),
+            message: e.to_string(),
+        })?;
+        let pid = child.id();
+        info!("Launched {} (PID {})", launch::program_name(&command), pid);
+        #[cfg(windows)]
+        if let Err(e) = resource_limits::kill_on_close(pid) {
+            warn!("{} will keep running if TaskTide exits: {}", launch::program_name(&command), e);
+        }
+        self.launched.insert(pid, Launched { child, trigger: Trigger::User, started, command });
+        // Listed right away, or clients would take it for exited until the next tick
+        self.refresh();
+        if let Some(limit) = limit {
+            // Running without the limit is not what was asked for
+            if let Err(e) = self.set_deadline(pid, limit) {
+                if let Some(mut launched) = self.launched.remove(&pid) {
+                    let _ = launched.child.kill();
+                    let _ = launched.child.wait();
+                }
+                return Err(e);
+            }
+        }
+        Ok(pid)
+    }
+
+    /// Starts a recently ended process again with the command line, working directory and
+    /// environment it had. Returns the new PID.
+    pub fn relaunch(&mut self, pid: u32) -> Result<u32, BackendError> {
+        let index = self.recently_ended
+            .iter()
+            .rposition(|ended| ended.pid == pid)
+            .ok_or_else(|| BackendError::InvalidRequest(format!("process {} was not ended recently", pid)))?;
+        let started = Local::now();
+        let ended = &self.recently_ended[index];
+        let cwd = Some(ended.cwd.as_path()).filter(|cwd| cwd.is_dir());
+        let child = launch::spawn(&ended.command, cwd, &ended.env, false);
+        let outcome = match &child {
+            Ok(_) => Outcome::Succeeded,
+            Err(e) => Outcome::Failed { error: e.to_string() },
+        };
+        // The old process is gone, so its name comes from what was captured
+        audit::record(&AuditEntry {
+            started,
+            finished: Local::now(),
+            trigger: Trigger::User,
+            action: Action::Restart,
+            pid,
+            name: ended.name.clone(),
+            exe: ended.exe.clone(),
+            smart_save: SmartSave::NotAttempted,
+            outcome,
+        });
+        let child = child.map_err(|e| BackendError::LaunchFailed {
+            command: ended.name.clone(),
+            message: e.to_string(),
+        })?;
+
+        let Some(ended) = self.recently_ended.remove(index) else {
+            return Err(BackendError::NoSuchProcess { pid });
+        };
+        let new_pid = child.id();
+        info!("Relaunched {} (was PID {}) as PID {}", ended.name, pid, new_pid);
+        self.events.push(Event::ProcessRelaunched { pid, new_pid, name: ended.name.clone() });
+        self.launched.insert(new_pid, Launched { child, trigger: Trigger::User, started, command: ended.command });
+        // Like `launch`, listed before the caller's next snapshot
+        self.refresh();
+        Ok(new_pid)
+    }
+
+    // Records launched processes that have exited
+    fn reap_launched(&mut self) {
+        let mut exited = Vec::new();
+        for (&pid, launched) in &mut self.launched {
+            match launched.child.try_wait() {
+                Ok(Some(status)) => exited.push((pid, Some(status))),
+                Ok(None) => {}
+                Err(e) => {
+                    warn!("Lost track of launched process {}: {}", pid, e);
+                    exited.push((pid, None));
+                }
+            }
+        }
+        for (pid, status) in exited {
+            let Some(launched) = self.launched.remove(&pid) else {
+                continue;
+            };
+            let Some(status) = status else {
+                continue;
+            };
+            let name = launch::program_name(&launched.command);
+            info!("{} (PID {}) {}", name, pid, launch::describe_exit(status));
+            launch::record_exit(launched.trigger, launched.started, pid, &launched.command, status);
+            self.events.push(Event::ProcessExited { pid, name, status: launch::describe_exit(status) });
+        }
+    }
+
+    // Schedules relaunches for watched processes that went away and starts the ones that are due
+    fn run_watchdog(&mut self, running: &HashSet<String>) {
+        for lost in self.watchdog.observe(&self.system, &self.config.watchdog_rules) {
+            self.report_lost(lost);
+        }
+        for restart in self.watchdog.due(running) {
+            let started = Local::now();
+            match launch::spawn(&restart.command, Some(&restart.cwd), &[], false) {
+                Ok(child) => {
+                    let new_pid = child.id();
+                    info!("Restarted {} as PID {} (restart {})", restart.name, new_pid, restart.attempt);
+                    self.record_restart(&restart, started, Outcome::Succeeded);
+                    self.events.push(Event::ProcessRestarted {
+                        pid: restart.pid,
+                        new_pid,
+                        name: restart.name.clone(),
+                        attempt: restart.attempt,
+                    });
+                    self.launched.insert(new_pid, Launched {
+                        child,
+                        trigger: Trigger::Watchdog,
+                        started,
+                        command: restart.command,
+                    });
+                }
+                Err(e) => {
+                    warn!("Could not restart {}: {}", restart.name, e);
+                    self.record_restart(&restart, started, Outcome::Failed { error: e.to_string() });
+                    if let Some(lost) = self.watchdog.restart_failed(restart, &self.config.watchdog_rules) {
+                        self.report_lost(lost);
+                    }
+                }
+            }
+        }
+    }
+
+    fn report_lost(&mut self, lost: Lost) {
+        match lost {
+            Lost::Scheduled { pid, name, attempt, delay } => {
+                info!("{} (PID {}) went away, restart {} in {:.1}s", name, pid, attempt, delay.as_secs_f32());
+            }
+            Lost::GaveUp { pid, name, exe, restarts } => {
+                warn!("{} (PID {}) went away again after {} restarts, giving up", name, pid, restarts);
+                let now = Local::now();
+                audit::record(&AuditEntry {
+                    started: now,
+                    finished: now,
+                    trigger: Trigger::Watchdog,
+                    action: Action::Restart,
+                    pid,
+                    name,
+                    exe,
+                    smart_save: SmartSave::NotAttempted,
+                    outcome: Outcome::Refused { reason: format!("gave up after {} restarts", restarts) },
+                });
+            }
+        }
+    }
+
+    fn record_restart(&self, restart: &Restart, started: DateTime<Local>, outcome: Outcome) {
+        audit::record(&AuditEntry {
+            started,
+            finished: Local::now(),
+            trigger: Trigger::Watchdog,
+            action: Action::Restart,
+            pid: restart.pid,
+            name: restart.name.clone(),
+            exe: restart.command.first().cloned().unwrap_or_default(),
+            smart_save: SmartSave::NotAttempted,
+            outcome,
+        });
+    }
+
+    pub fn clear_deadline(&mut self, pid: u32) {
+        if let Some(task) = self.tasks.get_mut(&pid) {
+            if task.deadline.take().is_some() {
+                self.events.push(Event::DeadlineCleared { pid });
+            }
+        }
+    }
+
+    pub fn set_deadline_action(&mut self, pid: u32, action: DeadlineAction) {
+        if let Some(task) = self.tasks.get_mut(&pid) {
+            task.deadline_action = action;
+        }
+    }
+
+    pub fn start_focus(&mut self, session: FocusSession) {
+        info!("Focus session started until {}", session.ends_at.format("%H:%M:%S"));
+        self.events.push(Event::FocusStarted { ends_at: session.ends_at });
+        self.focus = Some(session);
+    }
+
+    pub fn end_focus_early(&mut self, confirmation: &str) -> Result<(), BackendError> {
+        if self.focus.as_ref().is_some_and(|session| session.can_end_early(confirmation)) {
+            info!("Focus session ended early");
+            self.end_focus_session();
+            Ok(())
+        } else {
+            Err(BackendError::ConfirmationRequired)
+        }
+    }
+
+    fn end_focus_session(&mut self) {
+        if let Some(session) = self.focus.take() {
+            for pid in session.suspended {
+                let _ = self.resume_process(pid);
+            }
+            self.events.push(Event::FocusEnded);
+        }
+    }
+
+    pub fn suspend_process(&mut self, pid: u32) -> Result<(), BackendError> {
+        if let Some(protection) = self.protection(pid) {
+            warn!("Refusing to suspend process {}: {}", pid, protection.reason());
+            return Err(BackendError::Protected { pid, reason: protection.reason().to_string() });
+        }
+        match process_control::suspend(pid) {
+            Ok(()) => {
+                info!("Process {} suspended", pid);
+                self.suspended.insert(pid);
+                self.events.push(Event::ProcessSuspended { pid });
+                if let Some(task) = self.tasks.get_mut(&pid) {
+                    task.status = ProcessStatus::Suspended;
+                }
+                Ok(())
+            }
+            Err(e) => {
+                error!("Failed to suspend process {}: {}", pid, e);
+                Err(BackendError::from_io(pid, &e))
+            }
+        }
+    }
+
+    pub fn resume_process(&mut self, pid: u32) -> Result<(), BackendError> {
+        match process_control::resume(pid) {
+            Ok(()) => {
+                info!("Process {} resumed", pid);
+                self.suspended.remove(&pid);
+                self.events.push(Event::ProcessResumed { pid });
+                if let Some(task) = self.tasks.get_mut(&pid) {
+                    task.status = task.get_status();
+                }
+                Ok(())
+            }
+            Err(e) => {
+                error!("Failed to resume process {}: {}", pid, e);
+                Err(BackendError::from_io(pid, &e))
+            }
+        }
+    }
+
+    pub fn set_priority(&mut self, pid: u32, priority: PriorityClass) -> Result<(), BackendError> {
+        if let Some(protection) = self.protection(pid) {
+            warn!("Refusing to change the priority of process {}: {}", pid, protection.reason());
+            return Err(BackendError::Protected { pid, reason: protection.reason().to_string() });
+        }
+        match process_control::set_priority(pid, priority) {
+            Ok(()) => {
+                info!("Process {} priority set to {}", pid, priority);
+                if let Some(task) = self.tasks.get_mut(&pid) {
+                    task.priority = Some(priority);
+                }
+                Ok(())
+            }
+            Err(e) => {
+                error!("Failed to set priority of process {}: {}", pid, e);
+                Err(BackendError::from_io(pid, &e))
+            }
+        }
+    }
+
+    /// Caps the process's memory and CPU, or lifts the caps when `limits` is empty.
+    pub fn set_limits(&mut self, pid: u32, limits: ResourceLimits) -> Result<(), BackendError> {
+        if let Some(protection) = self.protection(pid) {
+            warn!("Refusing to limit process {}: {}", pid, protection.reason());
+            return Err(BackendError::Protected { pid, reason: protection.reason().to_string() });
+        }
+        match resource_limits::apply(pid, &limits) {
+            Ok(()) => {
+                info!("Process {} limited to {}", pid, limits);
+                if let Some(task) = self.tasks.get_mut(&pid) {
+                    task.limits = limits;
+                }
+                Ok(())
+            }
+            Err(e) => {
+                error!("Failed to limit process {}: {}", pid, e);
+                Err(BackendError::from_io(pid, &e))
+            }
+        }
+    }
+
+    pub fn set_affinity(&mut self, pid: u32, cpus: &[usize]) -> Result<(), BackendError> {
+        if let Some(protection) = self.protection(pid) {
+            warn!("Refusing to change the CPU affinity of process {}: {}", pid, protection.reason());
+            return Err(BackendError::Protected { pid, reason: protection.reason().to_string() });
+        }
+        // Lists come from API clients and the config as well as the GUI's parse_cpu_list
+        let cpu_count = num_cpus::get();
+        if cpus.is_empty() || cpus.iter().any(|&cpu| cpu >= cpu_count) {
+            warn!("Not restricting process {} to CPUs {:?}: this machine has CPUs 0-{}", pid, cpus, cpu_count - 1);
+            return Err(BackendError::InvalidRequest(format!(
+                "CPU list {:?} is empty or names CPUs this machine does not have (0-{})",
+                cpus,
+                cpu_count - 1,
+            )));
+        }
+        match process_control::set_affinity(pid, cpus) {
+            Ok(()) => {
+                info!("Process {} restricted to CPUs {:?}", pid, cpus);
+                Ok(())
+            }
+            Err(e) => {
+                error!("Failed to set CPU affinity of process {}: {}", pid, e);
+                Err(BackendError::from_io(pid, &e))
+            }
+        }
+    }
+
+    // Saves a priority/affinity change as a rule for every future launch of the app
+    pub fn remember_process_rule(&mut self, pid: u32, update: impl FnOnce(&mut ProcessRule)) {
+        if let Some(task) = self.tasks.get(&pid) {
+            update(self.config.process_rules.entry(budget_key(&task.name)).or_default());
+            self.persist_config();
+        }
+    }
+
+    pub fn override_protection(&mut self, pid: u32, enabled: bool) -> Result<(), BackendError> {
+        if enabled && !protection::is_elevated() {
+            warn!("Not overriding protection of process {}: TaskTide is not running as administrator", pid);
+            return Err(BackendError::Protected {
+                pid,
+                reason: "overriding protection needs administrator rights".to_string(),
+            });
+        }
+        if enabled {
+            warn!("Protection overridden for process {}", pid);
+            self.protection_overrides.insert(pid);
+        } else {
+            self.protection_overrides.remove(&pid);
+        }
+        if let Some(task) = self.tasks.get_mut(&pid) {
+            task.protection_overridden = enabled;
+        }
+        Ok(())
+    }
+
+    pub fn toggle_protected_app(&mut self, name: &str) {
+        let list = &mut self.config.protected_processes;
+        if let Some(index) = list.iter().position(|protected| protected.eq_ignore_ascii_case(name)) {
+            list.remove(index);
+        } else if !name.is_empty() {
+            list.push(name.to_string());
+        }
+        self.persist_config();
+    }
+
+    pub fn set_daily_budget(&mut self, name: &str, minutes: u32) {
+        if !name.is_empty() {
+            self.config.daily_budgets.insert(budget_key(name), minutes);
+            self.persist_config();
+        }
+    }
+
+    pub fn clear_daily_budget(&mut self, name: &str) {
+        if self.config.daily_budgets.remove(&budget_key(name)).is_some() {
+            self.persist_config();
+        }
+    }
+
+    // Remembers the dialog's choice, including whether child processes go too
+    pub fn skip_end_task_confirmation(&mut self, name: &str, end_children: bool) {
+        let key = budget_key(name);
+        if !self.config.skip_end_task_confirmation.contains(&key) {
+            self.config.skip_end_task_confirmation.push(key.clone());
+        }
+        self.config.end_task_keep_children.retain(|kept| *kept != key);
+        if !end_children {
+            self.config.end_task_keep_children.push(key);
+        }
+        self.persist_config();
+    }
+
+    // Appends to the audit log; the process must still be in the task list
+    fn audit(
+        &self,
+        started: DateTime<Local>,
+        trigger: Trigger,
+        action: Action,
+        pid: u32,
+        smart_save: SmartSave,
+        outcome: Outcome,
+    ) {
+        let (name, exe) = self.tasks
+            .get(&pid)
+            .map(|task| (task.name.clone(), task.exe.clone()))
+            .unwrap_or_default();
+        audit::record(&AuditEntry {
+            started,
+            finished: Local::now(),
+            trigger,
+            action,
+            pid,
+            name,
+            exe,
+            smart_save,
+            outcome,
+        });
+    }
+
+    // Termination hooks that apply to ending `name` for `trigger`
+    fn termination_hooks(&self, name: &str, trigger: Trigger) -> Vec<TerminationHook> {
+        let key = budget_key(name);
+        self.config.termination_hooks
+            .iter()
+            .filter(|hook| hook.applies(&key, trigger))
+            .cloned()
+            .collect()
+    }
+
+    fn hook_env(&self, pid: u32, name: &str, exe: &str, trigger: Trigger) -> Vec<(&'static str, String)> {
+        let deadline = self.tasks.get(&pid).and_then(|task| task.deadline);
+        vec![
+            ("TASKTIDE_PID", pid.to_string()),
+            ("TASKTIDE_NAME", name.to_string()),
+            ("TASKTIDE_EXE", exe.to_string()),
+            ("TASKTIDE_REASON", trigger.key().to_string()),
+            ("TASKTIDE_DEADLINE", deadline.map(|deadline| deadline.to_rfc3339()).unwrap_or_default()),
+        ]
+    }
+
+    /// Before-hooks to run ahead of `terminate_process`; none for protected or missing processes.
+    pub fn before_hooks(&self, pid: u32, trigger: Trigger) -> BeforeHooks {
+        let process = self.system.process(Pid::from_u32(pid)).filter(|_| self.protection(pid).is_none());
+        let (name, exe) = process
+            .map(|process| (process.name().to_string(), process.exe().display().to_string()))
+            .unwrap_or_default();
+        let hooks = match process {
+            Some(_) => self.termination_hooks(&name, trigger)
+                .into_iter()
+                .filter_map(|hook| {
+                    let timeout = hook.timeout();
+                    hook.before.map(|command| (command, timeout, hook.can_veto))
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+        BeforeHooks { pid, env: self.hook_env(pid, &name, &exe, trigger), name, hooks }
+    }
+
+    /// Ends `pid` once its before-hooks have run; `vetoed_by` is what `BeforeHooks::run` returned.
+    pub fn terminate_process(
+        &mut self,
+        pid: u32,
+        save_first: bool,
+        trigger: Trigger,
+        vetoed_by: Option<String>,
+    ) -> Result<(), BackendError> {
+        let started = Local::now();
+        if let Some(protection) = self.protection(pid) {
+            warn!("Refusing to terminate process {}: {}", pid, protection.reason());
+            let result = Err(BackendError::Protected { pid, reason: protection.reason().to_string() });
+            self.audit(started, trigger, Action::Terminate, pid, SmartSave::NotAttempted, outcome(&result));
+            return result;
+        }
+
+        let Some(process) = self.system.process(Pid::from_u32(pid)) else {
+            warn!("Cannot end process {}: it is no longer running", pid);
+            return Err(BackendError::NoSuchProcess { pid });
+        };
+        let name = process.name().to_string();
+        let cmd = process.cmd().to_vec();
+        let exe = process.exe().display().to_string();
+        let cwd = process.cwd().to_path_buf();
+        let environ: Vec<(String, String)> = process.environ()
+            .iter()
+            .filter_map(|variable| variable.split_once('='))
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        info!("Ending {} (PID {}), triggered by {:?}", name, pid, trigger);
+        let event = Event::ProcessTerminated { pid, name: name.clone() };
+
+        let termination_hooks = self.termination_hooks(&name, trigger);
+        let mut env = self.hook_env(pid, &name, &exe, trigger);
+        if let Some(command) = vetoed_by {
+            self.refused.insert(pid, Instant::now());
+            let result = Err(BackendError::HookVeto { pid, command });
+            self.audit(started, trigger, Action::Terminate, pid, SmartSave::NotAttempted, outcome(&result));
+            return result;
+        }
+
+        // Try to save work if it's a supported application
+        let profile = smart_save::profile_for(&name, &self.config).filter(|_| save_first).cloned();
+        let mut smart_save_result = SmartSave::NotAttempted;
+        if let Some(profile) = &profile {
+            info!(target: "tasktide::smart_save", "Attempting to save work in {} before ending it ({} profile)", name, profile.name);
+            self.stats.smart_save_attempts += 1;
+            let check = SaveCheck::new(pid, profile.verify, &cmd);
+            let state = if smart_save::try_save_application_work(pid, &name, &exe, profile) {
+                // Give the application time to finish saving
+                let wait = profile.wait_after_save_ms.unwrap_or(self.config.smart_save.wait_after_save_ms);
+                let state = smart_save::wait_for_save(&check, Duration::from_millis(wait));
+                info!(target: "tasktide::smart_save", "Save attempt for {}: {}", name, state);
+                smart_save_result = match state {
+                    SaveState::Saved => SmartSave::Saved,
+                    SaveState::Unknown => SmartSave::Unknown,
+                    SaveState::Unsaved(_) | SaveState::SaveAsDialog { .. } => SmartSave::Unsaved,
+                };
+                state
+            } else {
+                warn!(target: "tasktide::smart_save", "Could not attempt to save work in {}", name);
+                smart_save_result = SmartSave::Failed;
+                SaveState::Unknown
+            };
+            if matches!(smart_save_result, SmartSave::Failed | SmartSave::Unsaved) {
+                self.stats.smart_save_failures += 1;
+            }
+
+            if let Some(reason) = state.refusal(self.config.smart_save.block_unverified) {
+                warn!(target: "tasktide::smart_save", "Not ending {} (PID {}): {}", name, pid, reason);
+                // Retried after the cooldown, which would otherwise stack up dialogs
+                if let SaveState::SaveAsDialog { title, opened_by_save: true } = &state {
+                    smart_save::close_dialog(pid, title);
+                }
+                self.refused.insert(pid, Instant::now());
+                let result = Err(BackendError::UnsavedWork { pid, reason });
+                self.audit(started, trigger, Action::Terminate, pid, smart_save_result, outcome(&result));
+                return result;
+            }
+        }
+
+        #[cfg(windows)]
+        let result = unsafe {
+            // Windows errors carry an HRESULT, the low word is the Win32 error code
+            let os_error = |e: windows::core::Error| io::Error::from_raw_os_error(e.code().0 & 0xFFFF);
+            let process_handle = OpenProcess(
+                PROCESS_ACCESS_RIGHTS(PROCESS_TERMINATE.0),
+                false,
+                pid
+            );
+
+            match process_handle {
+                Ok(handle) if !handle.is_invalid() => {
+                    // Try to save one more time before terminating
+                    if let Some(profile) = profile.as_ref().filter(|_| smart_save_result != SmartSave::Saved) {
+                        smart_save::try_save_application_work(pid, &name, &exe, profile);
+                        thread::sleep(Duration::from_millis(self.config.smart_save.wait_before_terminate_ms));
+                    }
+
+                    let result = if TerminateProcess(handle, 1).as_bool() {
+                        Ok(())
+                    } else {
+                        Err(os_error(windows::core::Error::from_win32()))
+                    };
+
+                    let _ = CloseHandle(HANDLE(handle.0));
+                    result
+                }
+                Ok(_) => Err(io::Error::from(io::ErrorKind::PermissionDenied)),
+                Err(e) => Err(os_error(e)),
+            }
+        };
+
+        #[cfg(unix)]
+        let result = process_control::terminate(pid);
+
+        let result = match result {
+            Ok(()) => {
+                info!("Process {} terminated", pid);
+                Ok(())
+            }
+            Err(e) => {
+                error!("Failed to terminate process {}: {}", pid, e);
+                Err(BackendError::from_io(pid, &e))
+            }
+        };
+        self.audit(started, trigger, Action::Terminate, pid, smart_save_result, outcome(&result));
+
+        // After-hooks don't hold up the scheduler
+        let after: Vec<(String, Duration)> = termination_hooks
+            .iter()
+            .filter_map(|hook| hook.after.clone().map(|command| (command, hook.timeout())))
+            .collect();
+        if !after.is_empty() {
+            env.push(("TASKTIDE_RESULT", if result.is_ok() { "succeeded" } else { "failed" }.to_string()));
+            thread::spawn(move || {
+                for (command, timeout) in after {
+                    if let Err(e) = hooks::run(&command, &env, timeout) {
+                        warn!("Hook `{}` after ending PID {} failed: {}", command, pid, e);
+                    }
+                }
+            });
+        }
+
+        // The task stays listed as Terminated/Failed until the next refresh shows whether it is gone
+        match result {
+            Ok(()) => {
+                self.stats.terminations += 1;
+                self.terminated.insert(pid);
+                self.termination_failed.remove(&pid);
+                self.watchdog.forget(pid);
+                // Without a command line it can't be started again
+                if !cmd.is_empty() {
+                    if self.recently_ended.len() >= MAX_RECENTLY_ENDED {
+                        self.recently_ended.pop_front();
+                    }
+                    self.recently_ended.push_back(Ended {
+                        pid,
+                        name: name.clone(),
+                        exe: exe.clone(),
+                        command: cmd,
+                        cwd,
+                        env: environ,
+                        ended_at: Local::now(),
+                    });
+                }
+                if let Some(task) = self.tasks.get_mut(&pid) {
+                    task.status = ProcessStatus::Terminated;
+                }
+                self.events.push(event);
+            }
+            Err(_) => {
+                self.stats.termination_failures += 1;
+                self.termination_failed.insert(pid);
+                self.refused.insert(pid, Instant::now());
+                if let Some(task) = self.tasks.get_mut(&pid) {
+                    task.status = ProcessStatus::Failed;
+                }
+            }
+        }
+        result
+    }
+}
+
+fn outcome(result: &Result<(), BackendError>) -> Outcome {
+    match result {
+        Ok(()) => Outcome::Succeeded,
+        Err(BackendError::Protected { reason, .. } | BackendError::UnsavedWork { reason, .. }) => {
+            Outcome::Refused { reason: reason.clone() }
+        }
+        Err(BackendError::HookVeto { command, .. }) => Outcome::Refused { reason: format!("vetoed by hook `{}`", command) },
+        Err(e) => Outcome::Failed { error: e.to_string() },
+    }
+}
@@ -0,0 +1,169 @@
+use crate::config::{ColorConfig, Config};
+use crate::palette::parse_hex;
+use std::collections::BTreeMap;
+
+// Names of the editable colors, in the order the settings page lists them
+pub const COLOR_FIELDS: [&str; 6] = ["background", "surface", "text", "accent", "warning", "success"];
+
+#[derive(Debug, Clone)]
+pub enum SettingsField {
+    RefreshInterval,
+    DeadlinePresets,
+    MemoryLimitPresets,
+    WaitAfterSave,
+    WaitBeforeTerminate,
+    Color(&'static str),
+    Category(String),
+}
+
+// What the settings page is editing; text stays as typed until it is saved
+#[derive(Debug, Clone)]
+pub struct SettingsForm {
+    pub refresh_interval_ms: String,
+    pub deadline_presets: String,
+    pub memory_limit_presets: String,
+    pub wait_after_save_ms: String,
+    pub wait_before_terminate_ms: String,
+    pub colors: ColorConfig,
+    // Comma-separated executable names by category
+    pub app_categories: BTreeMap<String, String>,
+    // Names of the enabled smart-save profiles
+    pub smart_save_profiles: Vec<String>,
+    pub block_unverified: bool,
+}
+
+impl SettingsForm {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            refresh_interval_ms: config.refresh_interval_ms.to_string(),
+            deadline_presets: config.deadline_presets
+                .iter()
+                .map(|minutes| minutes.to_string())
+                .collect::<Vec<_>>()
+                .join(", "),
+            memory_limit_presets: config.memory_limit_presets_mb
+                .iter()
+                .map(|megabytes| megabytes.to_string())
+                .collect::<Vec<_>>()
+                .join(", "),
+            wait_after_save_ms: config.smart_save.wait_after_save_ms.to_string(),
+            wait_before_terminate_ms: config.smart_save.wait_before_terminate_ms.to_string(),
+            colors: config.colors.clone(),
+            app_categories: config.app_categories
+                .iter()
+                .map(|(category, apps)| (category.clone(), apps.join(", ")))
+                .collect(),
+            smart_save_profiles: config.smart_save.profiles
+                .iter()
+                .filter(|profile| profile.enabled)
+                .map(|profile| profile.name.clone())
+                .collect(),
+            block_unverified: config.smart_save.block_unverified,
+        }
+    }
+
+    pub fn value(&self, field: &SettingsField) -> &str {
+        match field {
+            SettingsField::RefreshInterval => &self.refresh_interval_ms,
+            SettingsField::DeadlinePresets => &self.deadline_presets,
+            SettingsField::MemoryLimitPresets => &self.memory_limit_presets,
+            SettingsField::WaitAfterSave => &self.wait_after_save_ms,
+            SettingsField::WaitBeforeTerminate => &self.wait_before_terminate_ms,
+            SettingsField::Color(name) => color(&self.colors, name),
+            SettingsField::Category(category) => self.app_categories.get(category).map(String::as_str).unwrap_or(""),
+        }
+    }
+
+    pub fn set(&mut self, field: SettingsField, value: String) {
+        match field {
+            SettingsField::RefreshInterval => self.refresh_interval_ms = value,
+            SettingsField::DeadlinePresets => self.deadline_presets = value,
+            SettingsField::MemoryLimitPresets => self.memory_limit_presets = value,
+            SettingsField::WaitAfterSave => self.wait_after_save_ms = value,
+            SettingsField::WaitBeforeTerminate => self.wait_before_terminate_ms = value,
+            SettingsField::Color(name) => *color_mut(&mut self.colors, name) = value,
+            SettingsField::Category(category) => {
+                self.app_categories.insert(category, value);
+            }
+        }
+    }
+
+    pub fn toggle_smart_save(&mut self, profile: String, enabled: bool) {
+        self.smart_save_profiles.retain(|existing| *existing != profile);
+        if enabled {
+            self.smart_save_profiles.push(profile);
+        }
+    }
+
+    /// Writes the form into `config`, or says which field is invalid.
+    pub fn apply(&self, config: &mut Config) -> Result<(), String> {
+        config.refresh_interval_ms = parse_ms("Refresh interval", &self.refresh_interval_ms)?;
+        if config.refresh_interval_ms < 250 {
+            return Err("Refresh interval must be at least 250 ms".to_string());
+        }
+        config.deadline_presets = split_list(&self.deadline_presets)
+            .map(|minutes| match minutes.parse::<u32>() {
+                Ok(minutes) if minutes > 0 => Ok(minutes),
+                _ => Err(format!("Deadline button '{}' is not a whole number of minutes", minutes)),
+            })
+            .collect::<Result<_, _>>()?;
+        config.memory_limit_presets_mb = split_list(&self.memory_limit_presets)
+            .map(|megabytes| match megabytes.parse::<u64>() {
+                Ok(megabytes) if megabytes > 0 => Ok(megabytes),
+                _ => Err(format!("Memory limit button '{}' is not a whole number of megabytes", megabytes)),
+            })
+            .collect::<Result<_, _>>()?;
+        config.smart_save.wait_after_save_ms = parse_ms("Wait after saving", &self.wait_after_save_ms)?;
+        config.smart_save.wait_before_terminate_ms = parse_ms("Wait before ending", &self.wait_before_terminate_ms)?;
+        let mut colors = self.colors.clone();
+        for name in COLOR_FIELDS {
+            let value = color(&self.colors, name).trim();
+            if parse_hex(value).is_none() {
+                return Err(format!("Color '{}' for {} is not #rrggbb", value, name));
+            }
+            *color_mut(&mut colors, name) = value.to_string();
+        }
+        config.colors = colors;
+        config.app_categories = self.app_categories
+            .iter()
+            .map(|(category, apps)| (category.clone(), split_list(apps).map(str::to_string).collect()))
+            .collect();
+        config.smart_save.block_unverified = self.block_unverified;
+        for profile in &mut config.smart_save.profiles {
+            profile.enabled = self.smart_save_profiles.contains(&profile.name);
+        }
+        Ok(())
+    }
+}
+
+fn parse_ms(label: &str, value: &str) -> Result<u64, String> {
+    value.trim()
+        .parse()
+        .map_err(|_| format!("{} must be a number of milliseconds", label))
+}
+
+fn split_list(value: &str) -> impl Iterator<Item = &str> {
+    value.split(',').map(str::trim).filter(|item| !item.is_empty())
+}
+
+fn color<'a>(colors: &'a ColorConfig, name: &str) -> &'a str {
+    match name {
+        "background" => &colors.background,
+        "surface" => &colors.surface,
+        "text" => &colors.text,
+        "accent" => &colors.accent,
+        "warning" => &colors.warning,
+        _ => &colors.success,
+    }
+}
+
+fn color_mut<'a>(colors: &'a mut ColorConfig, name: &str) -> &'a mut String {
+    match name {
+        "background" => &mut colors.background,
+        "surface" => &mut colors.surface,
+        "text" => &mut colors.text,
+        "accent" => &mut colors.accent,
+        "warning" => &mut colors.warning,
+        _ => &mut colors.success,
+    }
+}
@@ -0,0 +1,509 @@
+use crate::config::Config;
+use crate::focus::exe_stem;
+use crate::hooks;
+use crate::window_info::{looks_unsaved, window_titles};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+#[cfg(windows)]
+use crate::window_info::{window_class, window_title, windows_of};
+#[cfg(windows)]
+use windows::Win32::Foundation::{HWND, LPARAM, WPARAM};
+#[cfg(windows)]
+use windows::Win32::System::Threading::{AttachThreadInput, GetCurrentThreadId};
+#[cfg(windows)]
+use windows::Win32::UI::WindowsAndMessaging::{
+    GetForegroundWindow, GetWindow, GetWindowThreadProcessId, IsIconic, PostMessageW,
+    SetForegroundWindow, ShowWindow, GW_OWNER, SW_RESTORE, WM_CLOSE,
+};
+#[cfg(windows)]
+use enigo::{Enigo, Key, KeyboardControllable};
+#[cfg(windows)]
+use log::{debug, error};
+use log::warn;
+
+// How a save attempt is confirmed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Verification {
+    #[default]
+    None,
+    // The unsaved marker (`*`, `•`) disappears from the window title
+    TitleMarker,
+    // A document named on the command line is written
+    FileModified,
+    // The profile's save command exited successfully
+    ExitStatus,
+}
+
+// How to save the work of one kind of application before it is ended
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SaveProfile {
+    pub name: String,
+    pub enabled: bool,
+    // Executable names, matched case-insensitively against the whole name; `.exe` is optional
+    pub exe: Vec<String>,
+    // Class of the main window, for apps whose title doesn't name them
+    pub window_class: Option<String>,
+    // Key chords pressed in order, like ["ctrl+s"] or ["alt+f", "s"]
+    pub keys: Vec<String>,
+    // Shell command that saves instead of the keys, run with TASKTIDE_PID, TASKTIDE_NAME
+    // and TASKTIDE_EXE set; the only way to save on Linux
+    pub command: Option<String>,
+    // Overrides `smart_save.wait_after_save_ms`
+    pub wait_after_save_ms: Option<u64>,
+    pub verify: Verification,
+}
+
+impl Default for SaveProfile {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            enabled: true,
+            exe: Vec::new(),
+            window_class: None,
+            keys: vec!["ctrl+s".to_string()],
+            command: None,
+            wait_after_save_ms: None,
+            verify: Verification::None,
+        }
+    }
+}
+
+impl SaveProfile {
+    pub fn matches(&self, process_name: &str) -> bool {
+        let process = exe_stem(process_name);
+        self.exe.iter().any(|exe| exe_stem(exe) == process)
+    }
+}
+
+fn profile(name: &str, exe: &[&str], window_class: Option<&str>, verify: Verification) -> SaveProfile {
+    SaveProfile {
+        name: name.to_string(),
+        exe: exe.iter().map(|exe| exe.to_string()).collect(),
+        window_class: window_class.map(str::to_string),
+        verify,
+        ..SaveProfile::default()
+    }
+}
+
+/// The built-in profiles, the default for `smart_save.profiles`. Apps with a known
+/// window class come first so they win over the broader lists.
+pub fn default_profiles() -> Vec<SaveProfile> {
+    vec![
+        profile("Microsoft Word", &["WINWORD.EXE"], Some("OpusApp"), Verification::None),
+        profile("Microsoft Excel", &["EXCEL.EXE"], Some("XLMAIN"), Verification::None),
+        profile("Microsoft PowerPoint", &["POWERPNT.EXE"], Some("PPTFrameClass"), Verification::None),
+        profile("Notepad", &["notepad.exe"], Some("Notepad"), Verification::TitleMarker),
+        profile("Notepad++", &["notepad++.exe"], Some("Notepad++"), Verification::TitleMarker),
+        profile("Office", &[
+            "ONENOTE.EXE", "OUTLOOK.EXE", "PUBLISHER.EXE", "MSACCESS.EXE",
+            "swriter.exe", "scalc.exe", "simpress.exe",
+        ], None, Verification::None),
+        profile("Text editors", &[
+            "sublime_text.exe", "Code.exe", "vscode.exe", "atom.exe", "vim.exe", "gvim.exe",
+            "emacs.exe", "wordpad.exe",
+        ], None, Verification::TitleMarker),
+        profile("IDEs", &[
+            "devenv.exe", "idea64.exe", "pycharm64.exe", "webstorm64.exe", "rider64.exe",
+            "eclipse.exe", "android studio.exe", "androidstudio64.exe", "netbeans64.exe",
+        ], None, Verification::TitleMarker),
+        profile("Design", &[
+            "photoshop.exe", "illustrator.exe", "gimp-2.10.exe", "inkscape.exe", "figma.exe",
+            "xd.exe", "krita.exe", "paint.net.exe", "designer.exe",
+        ], None, Verification::None),
+        profile("Development tools", &[
+            "ssms.exe", "pgadmin4.exe", "dbeaver.exe", "postman.exe", "insomnia.exe",
+            "sourcetree.exe", "github desktop.exe",
+        ], None, Verification::None),
+        profile("Creative", &[
+            "premiere.exe", "aftereffects.exe", "audition.exe", "vegas.exe", "resolve.exe",
+            "blender.exe", "maya.exe", "3dsmax.exe",
+        ], None, Verification::None),
+        command_profile("Emacs", &["emacs"], EMACS_SAVE_COMMAND),
+        command_profile("Vim", &["vim", "gvim"], VIM_SAVE_COMMAND),
+    ]
+}
+
+// Only the server running as the process being ended is asked to save; with none the save fails
+const EMACS_SAVE_COMMAND: &str =
+    "[ \"$(emacsclient --eval '(emacs-pid)')\" = \"$TASKTIDE_PID\" ] && emacsclient --eval '(save-some-buffers t)'";
+const VIM_SAVE_COMMAND: &str = "vim --serverlist | { while read -r server; do \
+    if [ \"$(vim --servername \"$server\" --remote-expr 'getpid()')\" = \"$TASKTIDE_PID\" ]; then \
+    vim --servername \"$server\" --remote-send '<C-\\><C-N>:wa<CR>'; exit; fi; done; exit 1; }";
+
+fn command_profile(name: &str, exe: &[&str], command: &str) -> SaveProfile {
+    SaveProfile {
+        command: Some(command.to_string()),
+        keys: Vec::new(),
+        ..profile(name, exe, None, Verification::ExitStatus)
+    }
+}
+
+// One step of a save key sequence
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(not(windows), allow(dead_code))]
+pub struct KeyChord {
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+    // A single character, or enter, tab, escape, f1..f12
+    pub key: String,
+}
+
+/// Parses a chord such as `ctrl+s`, `ctrl+shift+s` or `f12`.
+pub fn parse_chord(chord: &str) -> Option<KeyChord> {
+    let mut parsed = KeyChord { ctrl: false, alt: false, shift: false, key: String::new() };
+    let mut parts: Vec<String> = chord.split('+').map(|part| part.trim().to_lowercase()).collect();
+    let key = parts.pop()?;
+    for modifier in parts {
+        match modifier.as_str() {
+            "ctrl" | "control" => parsed.ctrl = true,
+            "alt" => parsed.alt = true,
+            "shift" => parsed.shift = true,
+            _ => return None,
+        }
+    }
+    let named = matches!(key.as_str(), "enter" | "tab" | "escape")
+        || key.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()).is_some_and(|n| (1..=12).contains(&n));
+    if key.chars().count() != 1 && !named {
+        return None;
+    }
+    parsed.key = key;
+    Some(parsed)
+}
+
+#[cfg(windows)]
+fn enigo_key(key: &str) -> Option<Key> {
+    Some(match key {
+        "enter" => Key::Return,
+        "tab" => Key::Tab,
+        "escape" => Key::Escape,
+        "f1" => Key::F1,
+        "f2" => Key::F2,
+        "f3" => Key::F3,
+        "f4" => Key::F4,
+        "f5" => Key::F5,
+        "f6" => Key::F6,
+        "f7" => Key::F7,
+        "f8" => Key::F8,
+        "f9" => Key::F9,
+        "f10" => Key::F10,
+        "f11" => Key::F11,
+        "f12" => Key::F12,
+        _ => Key::Layout(key.chars().next()?),
+    })
+}
+
+#[cfg(windows)]
+fn send_keys(enigo: &mut Enigo, profile: &SaveProfile) -> bool {
+    let Some(chords) = profile.keys.iter().map(|chord| parse_chord(chord)).collect::<Option<Vec<_>>>() else {
+        error!("Smart save profile '{}' has an invalid key sequence {:?}", profile.name, profile.keys);
+        return false;
+    };
+    for chord in chords {
+        let Some(key) = enigo_key(&chord.key) else {
+            return false;
+        };
+        let modifiers: Vec<Key> = [(chord.ctrl, Key::Control), (chord.alt, Key::Alt), (chord.shift, Key::Shift)]
+            .into_iter()
+            .filter(|(held, _)| *held)
+            .map(|(_, modifier)| modifier)
+            .collect();
+        for modifier in &modifiers {
+            enigo.key_down(*modifier);
+        }
+        enigo.key_click(key);
+        for modifier in modifiers.iter().rev() {
+            enigo.key_up(*modifier);
+        }
+    }
+    true
+}
+
+// The window the save keys go to: the profile's window class if it has one,
+// otherwise the process's first titled top-level window
+#[cfg(windows)]
+fn main_window(pid: u32, profile: &SaveProfile) -> Option<HWND> {
+    let windows = windows_of(pid);
+    let by_class = profile.window_class.as_deref().and_then(|class| {
+        windows.iter().copied().find(|&hwnd| window_class(hwnd).eq_ignore_ascii_case(class))
+    });
+    by_class.or_else(|| {
+        windows.iter().copied().find(|&hwnd| {
+            unsafe { GetWindow(hwnd, GW_OWNER).0 == 0 } && !window_title(hwnd).is_empty()
+        })
+    })
+}
+
+// Brings the window to the foreground and reports whether it got there
+#[cfg(windows)]
+unsafe fn activate(hwnd: HWND) -> bool {
+    if IsIconic(hwnd).as_bool() {
+        ShowWindow(hwnd, SW_RESTORE);
+    }
+    // Only the foreground thread may hand out focus, borrow its input state for the switch
+    let current_thread = GetCurrentThreadId();
+    let foreground_thread = GetWindowThreadProcessId(GetForegroundWindow(), None);
+    let attached = foreground_thread != 0
+        && foreground_thread != current_thread
+        && AttachThreadInput(current_thread, foreground_thread, true).as_bool();
+    SetForegroundWindow(hwnd);
+    if attached {
+        AttachThreadInput(current_thread, foreground_thread, false);
+    }
+
+    for _ in 0..10 {
+        if GetForegroundWindow() == hwnd {
+            return true;
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+    false
+}
+
+/// Runs the profile's save command, or sends its keys where there is none.
+pub fn try_save_application_work(pid: u32, process_name: &str, exe: &str, profile: &SaveProfile) -> bool {
+    let Some(command) = &profile.command else {
+        return send_save_keys(pid, process_name, profile);
+    };
+    let env = [
+        ("TASKTIDE_PID", pid.to_string()),
+        ("TASKTIDE_NAME", process_name.to_string()),
+        ("TASKTIDE_EXE", exe.to_string()),
+    ];
+    match hooks::run(command, &env, COMMAND_TIMEOUT) {
+        Ok(status) => status.success(),
+        Err(e) => {
+            warn!("Save command for {} (PID {}) failed: {}", process_name, pid, e);
+            false
+        }
+    }
+}
+
+/// Sends the profile's save keys to the main window of `pid`. Keys are only sent once
+/// that window is in the foreground, and focus goes back to where it was afterwards.
+#[cfg(windows)]
+fn send_save_keys(pid: u32, process_name: &str, profile: &SaveProfile) -> bool {
+    let Some(window) = main_window(pid, profile) else {
+        error!("Could not find a window of {} (PID {})", process_name, pid);
+        return false;
+    };
+
+    unsafe {
+        let previous = GetForegroundWindow();
+        if !activate(window) {
+            warn!("Could not bring {} (PID {}) to the foreground, not sending save keys", process_name, pid);
+            return false;
+        }
+
+        let mut enigo = Enigo::new();
+        let sent = send_keys(&mut enigo, profile);
+        if sent {
+            debug!("Sent {} to {} (PID {})", profile.keys.join(", "), process_name, pid);
+        }
+
+        // Let the app take the keys before focus moves away again
+        thread::sleep(Duration::from_millis(100));
+        if previous.0 != 0 && previous != window {
+            activate(previous);
+        }
+        sent
+    }
+}
+
+#[cfg(not(windows))]
+fn send_save_keys(_pid: u32, process_name: &str, profile: &SaveProfile) -> bool {
+    warn!("Can't send keys to {} on this platform, give the '{}' profile a command", process_name, profile.name);
+    false
+}
+
+// Window titles that mean the app is asking where to save
+const SAVE_DIALOG_TITLES: &[&str] = &["save as", "save file", "save a copy"];
+
+// A save command still running after this is killed and counts as failed
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(10);
+
+// How often a pending save is checked on
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+// What a save attempt achieved, as far as it can be told
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SaveState {
+    Saved,
+    Unsaved(String),
+    Unknown,
+    // The app is waiting in a Save As dialog; `opened_by_save` when the save keys opened it
+    // rather than the user
+    SaveAsDialog { title: String, opened_by_save: bool },
+}
+
+impl SaveState {
+    /// Why the process must not be ended after this save attempt, if it mustn't.
+    pub fn refusal(&self, block_unverified: bool) -> Option<String> {
+        match self {
+            SaveState::Saved => None,
+            SaveState::SaveAsDialog { title, opened_by_save: false } => {
+                Some(format!("it is asking where to save (\"{}\")", title))
+            }
+            _ if block_unverified => Some(format!("its work could not be confirmed saved ({})", self)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for SaveState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveState::Saved => write!(f, "saved"),
+            SaveState::Unsaved(reason) => write!(f, "unsaved, {}", reason),
+            SaveState::Unknown => write!(f, "unknown"),
+            SaveState::SaveAsDialog { title, opened_by_save: false } => write!(f, "\"{}\" dialog is open", title),
+            SaveState::SaveAsDialog { title, opened_by_save: true } => write!(f, "saving opened \"{}\"", title),
+        }
+    }
+}
+
+fn modified(path: &PathBuf) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+// Taken before the save keys are sent, so `check` can tell what changed
+pub struct SaveCheck {
+    pid: u32,
+    verify: Verification,
+    // Documents named on the command line and when they were last written
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+    // Save dialogs the user already had open
+    dialogs_before: Vec<String>,
+}
+
+fn save_dialog(titles: &[String]) -> Option<&String> {
+    titles.iter().find(|title| {
+        let lower = title.to_lowercase();
+        SAVE_DIALOG_TITLES.iter().any(|dialog| lower.contains(dialog))
+    })
+}
+
+impl SaveCheck {
+    pub fn new(pid: u32, verify: Verification, cmd: &[String]) -> Self {
+        let files = match verify {
+            Verification::FileModified => cmd
+                .iter()
+                .skip(1)
+                .map(PathBuf::from)
+                .filter(|path| path.is_file())
+                .map(|path| {
+                    let modified = modified(&path);
+                    (path, modified)
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        let dialogs_before = save_dialog(&window_titles(pid)).cloned().into_iter().collect();
+        Self { pid, verify, files, dialogs_before }
+    }
+
+    pub fn check(&self) -> SaveState {
+        let titles = window_titles(self.pid);
+        if let Some(title) = save_dialog(&titles) {
+            return SaveState::SaveAsDialog {
+                title: title.clone(),
+                opened_by_save: !self.dialogs_before.contains(title),
+            };
+        }
+
+        match self.verify {
+            Verification::None => SaveState::Unknown,
+            // Only checked once the command has succeeded
+            Verification::ExitStatus => SaveState::Saved,
+            // No titles to look at (no windows, or not Windows) says nothing either way
+            Verification::TitleMarker if titles.is_empty() => SaveState::Unknown,
+            Verification::TitleMarker => match titles.iter().find(|title| looks_unsaved(title)) {
+                Some(title) => SaveState::Unsaved(format!("\"{}\" still shows unsaved changes", title)),
+                None => SaveState::Saved,
+            },
+            Verification::FileModified if self.files.is_empty() => SaveState::Unknown,
+            Verification::FileModified => {
+                if self.files.iter().any(|(path, before)| modified(path) > *before) {
+                    SaveState::Saved
+                } else {
+                    SaveState::Unsaved("the document was not written".to_string())
+                }
+            }
+        }
+    }
+}
+
+/// Checks on the save until it is confirmed, a Save As dialog shows up or `wait` is over.
+pub fn wait_for_save(check: &SaveCheck, wait: Duration) -> SaveState {
+    let deadline = Instant::now() + wait;
+    loop {
+        let state = check.check();
+        let now = Instant::now();
+        if matches!(state, SaveState::Saved | SaveState::SaveAsDialog { .. }) || now >= deadline {
+            return state;
+        }
+        thread::sleep(POLL_INTERVAL.min(deadline - now));
+    }
+}
+
+/// Closes a Save As dialog the save keys opened, so a process that is left running isn't
+/// left waiting in it.
+#[cfg(windows)]
+pub fn close_dialog(pid: u32, title: &str) {
+    for window in windows_of(pid) {
+        if window_title(window) == title {
+            debug!("Closing \"{}\" opened by the save attempt", title);
+            unsafe {
+                PostMessageW(window, WM_CLOSE, WPARAM(0), LPARAM(0));
+            }
+        }
+    }
+}
+
+#[cfg(not(windows))]
+pub fn close_dialog(_pid: u32, _title: &str) {}
+
+/// The first enabled profile that covers the executable, if any.
+pub fn profile_for<'a>(process_name: &str, config: &'a Config) -> Option<&'a SaveProfile> {
+    config.smart_save.profiles
+        .iter()
+        .find(|profile| profile.enabled && profile.matches(process_name))
+}
+
+pub fn should_try_save(process_name: &str, config: &Config) -> bool {
+    profile_for(process_name, config).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profiles_match_whole_executable_names() {
+        let vim = profile("Vim", &["vim", "gvim"], None, Verification::None);
+        for name in ["vim", "gvim", "VIM.EXE", "gvim.exe"] {
+            assert!(vim.matches(name), "{}", name);
+        }
+        for name in ["nvim", "vimdiff", "evim", "vi"] {
+            assert!(!vim.matches(name), "{}", name);
+        }
+        let word = profile("Microsoft Word", &["WINWORD.EXE"], None, Verification::None);
+        assert!(word.matches("winword.exe"));
+        assert!(word.matches("WINWORD"));
+        assert!(!word.matches("WINWORD.EXE.bak"));
+    }
+
+    #[test]
+    fn only_a_dialog_the_user_opened_vetoes() {
+        let dialog = |opened_by_save| SaveState::SaveAsDialog { title: "Save As".into(), opened_by_save };
+        assert!(dialog(false).refusal(false).is_some());
+        assert!(dialog(true).refusal(false).is_none());
+        assert!(dialog(true).refusal(true).is_some());
+    }
+}
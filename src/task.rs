@@ -0,0 +1,338 @@
+use crate::process_control::PriorityClass;
+use crate::protection::Protection;
+use crate::resource_limits::ResourceLimits;
+use crate::ProcessIcon;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Task {
+    pub name: String,
+    pub pid: u32,
+    pub exe: String,
+    pub parent: Option<u32>,
+    // Seconds since the epoch, to tell a real child from one whose parent PID was reused
+    pub start_time: u64,
+    pub cpu_usage: f32,
+    pub memory_usage: u64,
+    #[serde(skip)]
+    pub icon: Option<ProcessIcon>,
+    pub deadline: Option<DateTime<Local>>,
+    pub deadline_action: DeadlineAction,
+    pub budget_remaining: Option<Duration>,
+    pub priority: Option<PriorityClass>,
+    // Memory and CPU caps TaskTide put on the process
+    pub limits: ResourceLimits,
+    // Protection before any override, and whether the user overrode it
+    pub protection: Option<Protection>,
+    pub protection_overridden: bool,
+    pub status: ProcessStatus,
+}
+
+impl Task {
+    pub fn new(name: String, pid: u32, cpu_usage: f32, memory_usage: u64) -> Self {
+        Self {
+            name,
+            pid,
+            exe: String::new(),
+            parent: None,
+            start_time: 0,
+            cpu_usage,
+            memory_usage,
+            icon: None,
+            deadline: None,
+            deadline_action: DeadlineAction::Terminate,
+            budget_remaining: None,
+            priority: None,
+            limits: ResourceLimits::default(),
+            protection: None,
+            protection_overridden: false,
+            status: ProcessStatus::Running,
+        }
+    }
+
+    pub fn get_status(&self) -> ProcessStatus {
+        if let Some(deadline) = self.deadline {
+            let now = Local::now();
+            if now > deadline {
+                ProcessStatus::DeadlineReached
+            } else {
+                ProcessStatus::Running
+            }
+        } else {
+            ProcessStatus::Running
+        }
+    }
+
+    pub fn format_deadline(&self) -> String {
+        if let Some(deadline) = self.deadline {
+            let now = Local::now();
+            if now > deadline {
+                "Expired".to_string()
+            } else {
+                let remaining = deadline.signed_duration_since(now);
+                let minutes = remaining.num_minutes();
+                let seconds = remaining.num_seconds() % 60;
+                if minutes > 0 {
+                    format!("{}m {}s left", minutes, seconds)
+                } else {
+                    format!("{}s left", seconds)
+                }
+            }
+        } else if let Some(remaining) = self.budget_remaining {
+            let secs = remaining.as_secs();
+            if secs == 0 {
+                "Budget used up".to_string()
+            } else if secs >= 3600 {
+                format!("Budget: {}h {}m", secs / 3600, (secs % 3600) / 60)
+            } else {
+                format!("Budget: {}m {}s", secs / 60, secs % 60)
+            }
+        } else {
+            "None".to_string()
+        }
+    }
+
+    // Protection that is still in force, i.e. not overridden by the user
+    pub fn effective_protection(&self) -> Option<Protection> {
+        self.protection
+            .filter(|protection| !(protection.can_override() && self.protection_overridden))
+    }
+}
+
+// All descendants of the process, deepest first so they can be ended bottom-up. Parent PIDs
+// can be stale or reused, so a child must not be older than its parent and each process is
+// visited once, even if the PIDs form a cycle.
+pub fn descendants<'a>(tasks: impl Iterator<Item = &'a Task>, pid: u32) -> Vec<(u32, String)> {
+    let tasks: Vec<&Task> = tasks.collect();
+    let mut visited = HashSet::from([pid]);
+    let mut queue = VecDeque::from([pid]);
+    // Breadth-first, so reversed every process comes after all of its descendants
+    let mut children = Vec::new();
+    while let Some(parent) = queue.pop_front() {
+        let started = tasks.iter().find(|task| task.pid == parent).map(|task| task.start_time);
+        for task in &tasks {
+            if task.parent == Some(parent)
+                && started.is_none_or(|started| task.start_time >= started)
+                && visited.insert(task.pid)
+            {
+                children.push((task.pid, task.name.clone()));
+                queue.push_back(task.pid);
+            }
+        }
+    }
+    children.reverse();
+    children
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum TimeInterval {
+    // Counted from when the scheduler receives it
+    Minutes(u32),
+    Custom(DateTime<Local>),
+}
+
+impl TimeInterval {
+    pub fn deadline(&self) -> DateTime<Local> {
+        match self {
+            TimeInterval::Minutes(minutes) => Local::now() + chrono::Duration::minutes(*minutes as i64),
+            TimeInterval::Custom(deadline) => *deadline,
+        }
+    }
+}
+
+// Short label such as 30m, 2h or 1h30m
+pub fn format_minutes(minutes: u32) -> String {
+    match (minutes / 60, minutes % 60) {
+        (0, minutes) => format!("{}m", minutes),
+        (hours, 0) => format!("{}h", hours),
+        (hours, minutes) => format!("{}h{}m", hours, minutes),
+    }
+}
+
+// A positive whole number of minutes, as typed into the budget and focus fields
+pub fn parse_minutes(input: &str) -> Option<u32> {
+    input.trim().parse::<u32>().ok().filter(|&minutes| minutes > 0)
+}
+
+// Longest duration accepted; a year is plenty and keeps `now + duration` far from overflowing
+const MAX_DURATION_DAYS: i64 = 365;
+
+// Accepts 45m, 2h, 90s, 1h30m, or a bare number of minutes like the GUI. Zero, negative
+// and overlong durations are rejected like malformed ones.
+pub fn parse_duration(input: &str) -> Option<chrono::Duration> {
+    let total = if let Ok(minutes) = input.parse::<i64>() {
+        chrono::Duration::try_minutes(minutes)?
+    } else {
+        let mut total = chrono::Duration::zero();
+        let mut number = String::new();
+        for c in input.chars() {
+            if c.is_ascii_digit() {
+                number.push(c);
+                continue;
+            }
+            let value: i64 = number.parse().ok()?;
+            number.clear();
+            let part = match c {
+                'h' => chrono::Duration::try_hours(value),
+                'm' => chrono::Duration::try_minutes(value),
+                's' => chrono::Duration::try_seconds(value),
+                _ => return None,
+            }?;
+            total = total.checked_add(&part)?;
+        }
+        if !number.is_empty() {
+            return None;
+        }
+        total
+    };
+    within_limits(total)
+}
+
+/// A whole number of minutes as sent by API clients, held to the same limits as `parse_duration`.
+pub fn minutes_duration(minutes: i64) -> Option<chrono::Duration> {
+    within_limits(chrono::Duration::try_minutes(minutes)?)
+}
+
+fn within_limits(duration: chrono::Duration) -> Option<chrono::Duration> {
+    (duration > chrono::Duration::zero() && duration <= chrono::Duration::days(MAX_DURATION_DAYS)).then_some(duration)
+}
+
+// What happens to a process once its deadline or a focus session catches it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DeadlineAction {
+    Terminate,
+    Suspend,
+    LowerPriority,
+}
+
+impl DeadlineAction {
+    pub const ALL: [DeadlineAction; 3] = [
+        DeadlineAction::Terminate,
+        DeadlineAction::Suspend,
+        DeadlineAction::LowerPriority,
+    ];
+}
+
+impl fmt::Display for DeadlineAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            DeadlineAction::Terminate => "End Task",
+            DeadlineAction::Suspend => "Suspend",
+            DeadlineAction::LowerPriority => "Idle Priority",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ProcessStatus {
+    Running,
+    Suspended,
+    Terminated,
+    // An attempt to end it failed
+    Failed,
+    DeadlineReached,
+}
+
+impl ProcessStatus {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ProcessStatus::Running => "Running",
+            ProcessStatus::Suspended => "Suspended",
+            ProcessStatus::Terminated => "Terminated",
+            ProcessStatus::Failed => "Failed",
+            ProcessStatus::DeadlineReached => "Deadline",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(pid: u32, parent: u32, start_time: u64) -> Task {
+        Task { parent: Some(parent), start_time, ..Task::new(format!("p{}", pid), pid, 0.0, 0) }
+    }
+
+    fn pids(children: Vec<(u32, String)>) -> Vec<u32> {
+        children.into_iter().map(|(pid, _)| pid).collect()
+    }
+
+    #[test]
+    fn descendants_come_deepest_first() {
+        let tasks = [task(1, 0, 10), task(2, 1, 11), task(3, 2, 12), task(4, 1, 13), task(5, 3, 14)];
+        let children = pids(descendants(tasks.iter(), 1));
+        assert_eq!(children.len(), 4);
+        let position = |pid| children.iter().position(|&child| child == pid).unwrap();
+        assert!(position(5) < position(3) && position(3) < position(2));
+    }
+
+    #[test]
+    fn descendants_survive_parent_cycles() {
+        // 1 -> 2 -> 3 -> 1, as reused PIDs can make it look
+        let tasks = [task(1, 3, 10), task(2, 1, 10), task(3, 2, 10)];
+        assert_eq!(pids(descendants(tasks.iter(), 1)), [3, 2]);
+    }
+
+    #[test]
+    fn descendants_skip_processes_older_than_their_parent() {
+        // 7 names 1 as its parent, but 1 is a newer process that reused the PID
+        let tasks = [task(1, 0, 100), task(7, 1, 50), task(8, 1, 150)];
+        assert_eq!(pids(descendants(tasks.iter(), 1)), [8]);
+    }
+
+    #[test]
+    fn minutes_duration_is_bounded() {
+        assert_eq!(minutes_duration(25), Some(chrono::Duration::minutes(25)));
+        assert_eq!(minutes_duration(MAX_DURATION_DAYS * 24 * 60), Some(chrono::Duration::days(MAX_DURATION_DAYS)));
+        for minutes in [0, -5, MAX_DURATION_DAYS * 24 * 60 + 1, i64::MAX, i64::MIN] {
+            assert_eq!(minutes_duration(minutes), None, "{}", minutes);
+        }
+    }
+
+    #[test]
+    fn parse_duration_accepts_bare_minutes() {
+        assert_eq!(parse_duration("45"), Some(chrono::Duration::minutes(45)));
+    }
+
+    #[test]
+    fn parse_duration_accepts_units() {
+        assert_eq!(parse_duration("2h"), Some(chrono::Duration::hours(2)));
+        assert_eq!(parse_duration("90s"), Some(chrono::Duration::seconds(90)));
+        assert_eq!(parse_duration("1h30m"), Some(chrono::Duration::minutes(90)));
+    }
+
+    #[test]
+    fn parse_duration_rejects_zero() {
+        assert_eq!(parse_duration("0"), None);
+        assert_eq!(parse_duration("0m"), None);
+        assert_eq!(parse_duration("0h0m0s"), None);
+    }
+
+    #[test]
+    fn parse_duration_rejects_negatives() {
+        assert_eq!(parse_duration("-5"), None);
+        assert_eq!(parse_duration("-5m"), None);
+    }
+
+    #[test]
+    fn parse_duration_rejects_malformed() {
+        assert_eq!(parse_duration(""), None);
+        assert_eq!(parse_duration("m"), None);
+        assert_eq!(parse_duration("1h30"), None);
+        assert_eq!(parse_duration("2d"), None);
+    }
+
+    #[test]
+    fn parse_duration_rejects_overflow() {
+        assert_eq!(parse_duration("99999999999999999999"), None);
+        assert_eq!(parse_duration("9223372036854775807"), None);
+        assert_eq!(parse_duration("9223372036854775807h"), None);
+        assert_eq!(parse_duration("9999999999h"), None);
+        assert_eq!(parse_duration("8784h1m"), None);
+    }
+}
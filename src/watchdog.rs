@@ -0,0 +1,158 @@
+use crate::config::WatchdogRule;
+use crate::quota::budget_key;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use sysinfo::{Pid, PidExt, ProcessExt, ProcessStatus, System, SystemExt};
+
+// A running process covered by a watchdog rule, with what it takes to start it again
+struct Watched {
+    key: String,
+    name: String,
+    command: Vec<String>,
+    cwd: PathBuf,
+    started: Instant,
+}
+
+/// A relaunch waiting out its backoff.
+#[derive(Debug, Clone)]
+pub struct Restart {
+    // The process that went away
+    pub pid: u32,
+    pub name: String,
+    pub command: Vec<String>,
+    pub cwd: PathBuf,
+    // 1 for the first restart since the process last ran long enough
+    pub attempt: u32,
+    key: String,
+    due: Instant,
+}
+
+/// What the watchdog decided about a process that went away.
+pub enum Lost {
+    Scheduled { pid: u32, name: String, attempt: u32, delay: Duration },
+    GaveUp { pid: u32, name: String, exe: String, restarts: u32 },
+}
+
+// Notices watched processes disappearing and schedules their relaunch with exponential backoff
+#[derive(Default)]
+pub struct Watchdog {
+    watched: HashMap<u32, Watched>,
+    // Ended by TaskTide and not to be watched again while they wind down
+    ended: HashSet<u32>,
+    pending: Vec<Restart>,
+    // Restarts since each rule's process last stayed up for `reset_after_ms`
+    restarts: HashMap<String, u32>,
+}
+
+impl Watchdog {
+    /// Starts watching new matches and returns what happened to the ones that are gone.
+    pub fn observe(&mut self, system: &System, rules: &[WatchdogRule]) -> Vec<Lost> {
+        self.ended.retain(|&pid| system.process(Pid::from_u32(pid)).is_some());
+        for process in system.processes().values() {
+            let pid = process.pid().as_u32();
+            let key = budget_key(process.name());
+            if self.watched.contains_key(&pid)
+                || self.ended.contains(&pid)
+                || process.status() == ProcessStatus::Zombie
+                || !rules.iter().any(|rule| rule.matches(&key))
+            {
+                continue;
+            }
+            // Without a command line there is nothing to relaunch
+            let command = if process.cmd().is_empty() {
+                match process.exe().to_str() {
+                    Some(exe) if !exe.is_empty() => vec![exe.to_string()],
+                    _ => continue,
+                }
+            } else {
+                process.cmd().to_vec()
+            };
+            self.watched.insert(pid, Watched {
+                key,
+                name: process.name().to_string(),
+                command,
+                cwd: process.cwd().to_path_buf(),
+                started: Instant::now(),
+            });
+        }
+
+        // A zombie has exited even though it is still listed
+        let gone: Vec<u32> = self.watched
+            .keys()
+            .copied()
+            .filter(|&pid| {
+                system
+                    .process(Pid::from_u32(pid))
+                    .is_none_or(|process| process.status() == ProcessStatus::Zombie)
+            })
+            .collect();
+        let mut lost = Vec::new();
+        for pid in gone {
+            let Some(watched) = self.watched.remove(&pid) else {
+                continue;
+            };
+            // The rule may have been removed since
+            let Some(rule) = rules.iter().find(|rule| rule.matches(&watched.key)) else {
+                continue;
+            };
+            if watched.started.elapsed() >= rule.reset_after() {
+                self.restarts.remove(&watched.key);
+            }
+            lost.extend(self.schedule(pid, watched, rule));
+        }
+        lost
+    }
+
+    /// Stops watching a process TaskTide itself ended, so it stays ended.
+    pub fn forget(&mut self, pid: u32) {
+        self.watched.remove(&pid);
+        self.ended.insert(pid);
+    }
+
+    /// Restarts whose backoff is over. Those whose program is running again are dropped.
+    pub fn due(&mut self, running: &HashSet<String>) -> Vec<Restart> {
+        let now = Instant::now();
+        let (due, pending): (Vec<_>, Vec<_>) = self.pending.drain(..).partition(|restart| restart.due <= now);
+        self.pending = pending;
+        due.into_iter().filter(|restart| !running.contains(&restart.key)).collect()
+    }
+
+    /// Counts a relaunch that could not start as another failure.
+    pub fn restart_failed(&mut self, restart: Restart, rules: &[WatchdogRule]) -> Option<Lost> {
+        let rule = rules.iter().find(|rule| rule.matches(&restart.key))?;
+        let watched = Watched {
+            key: restart.key,
+            name: restart.name,
+            command: restart.command,
+            cwd: restart.cwd,
+            started: Instant::now(),
+        };
+        self.schedule(restart.pid, watched, rule)
+    }
+
+    fn schedule(&mut self, pid: u32, watched: Watched, rule: &WatchdogRule) -> Option<Lost> {
+        // Several instances going down together need only one relaunch
+        if self.pending.iter().any(|restart| restart.key == watched.key) {
+            return None;
+        }
+        let restarts = self.restarts.entry(watched.key.clone()).or_insert(0);
+        if *restarts >= rule.max_restarts {
+            let exe = watched.command.first().cloned().unwrap_or_default();
+            return Some(Lost::GaveUp { pid, name: watched.name, exe, restarts: *restarts });
+        }
+        let delay = rule.backoff(*restarts);
+        *restarts += 1;
+        let attempt = *restarts;
+        self.pending.push(Restart {
+            pid,
+            name: watched.name.clone(),
+            command: watched.command,
+            cwd: watched.cwd,
+            attempt,
+            key: watched.key,
+            due: Instant::now() + delay,
+        });
+        Some(Lost::Scheduled { pid, name: watched.name, attempt, delay })
+    }
+}
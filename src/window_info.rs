@@ -0,0 +1,78 @@
+#[cfg(windows)]
+mod imp {
+    use windows::Win32::Foundation::{BOOL, HWND, LPARAM};
+    use windows::Win32::UI::WindowsAndMessaging::{
+        EnumWindows, GetClassNameW, GetWindowTextW, GetWindowThreadProcessId, IsWindowVisible,
+    };
+
+    struct Search {
+        pid: u32,
+        windows: Vec<HWND>,
+    }
+
+    unsafe extern "system" fn collect_windows(hwnd: HWND, lparam: LPARAM) -> BOOL {
+        let search = &mut *(lparam.0 as *mut Search);
+        let mut owner = 0u32;
+        GetWindowThreadProcessId(hwnd, Some(&mut owner));
+        if owner == search.pid && IsWindowVisible(hwnd).as_bool() {
+            search.windows.push(hwnd);
+        }
+        BOOL(1)
+    }
+
+    // Visible top-level windows owned by the process, in z-order
+    pub fn windows_of(pid: u32) -> Vec<HWND> {
+        let mut search = Search { pid, windows: Vec::new() };
+        unsafe {
+            EnumWindows(Some(collect_windows), LPARAM(&mut search as *mut Search as isize));
+        }
+        search.windows
+    }
+
+    pub fn window_title(hwnd: HWND) -> String {
+        let mut title: [u16; 512] = [0; 512];
+        let len = unsafe { GetWindowTextW(hwnd, &mut title) };
+        String::from_utf16_lossy(&title[..len.max(0) as usize])
+    }
+
+    pub fn window_class(hwnd: HWND) -> String {
+        let mut class: [u16; 256] = [0; 256];
+        let len = unsafe { GetClassNameW(hwnd, &mut class) };
+        String::from_utf16_lossy(&class[..len.max(0) as usize])
+    }
+
+    pub fn window_titles(pid: u32) -> Vec<String> {
+        windows_of(pid)
+            .into_iter()
+            .map(window_title)
+            .filter(|title| !title.is_empty())
+            .collect()
+    }
+}
+
+#[cfg(not(windows))]
+mod imp {
+    pub fn window_titles(_pid: u32) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+pub use imp::window_titles;
+#[cfg(windows)]
+pub use imp::{window_class, window_title, windows_of};
+
+// Editors commonly mark modified documents with these in their title bar
+const UNSAVED_MARKERS: &[&str] = &["*", "•", "●", "(modified)", "[modified]", "unsaved"];
+
+pub fn looks_unsaved(title: &str) -> bool {
+    let lower = title.to_lowercase();
+    UNSAVED_MARKERS.iter().any(|marker| lower.contains(marker))
+}
+
+/// Window titles of the process that look like they hold unsaved changes.
+pub fn unsaved_work_hints(pid: u32) -> Vec<String> {
+    window_titles(pid)
+        .into_iter()
+        .filter(|title| looks_unsaved(title))
+        .collect()
+}